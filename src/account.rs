@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    error::{Error, Result},
+    util::{b64, jwk, write_atomic},
+};
+
+/// The file that holds the PEM formatted account key.
+const ACCOUNT_KEY_FILE: &str = "account.key";
+/// The file that holds the information about the registered account.
+const ACCOUNT_INFO_FILE: &str = "account.json";
+
+/// The information about an account that's persisted next to its key.
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountInfo {
+    account_url: String,
+}
+
+/// A store that persists the account key and the account URL in a directory, so the same
+/// `ACME` account can be reused across runs instead of registering a new one every time.
+#[derive(Debug, Clone)]
+pub struct AccountStore {
+    path: PathBuf,
+}
+
+impl AccountStore {
+    /// Creates a store that keeps the account files in the given directory. The directory
+    /// is created once the account is saved.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        AccountStore { path: path.into() }
+    }
+
+    /// Creates a store in a sub directory of `base` that's named after the host of the
+    /// server, as an account is only valid for the server it was registered at.
+    pub fn for_server<P: AsRef<Path>>(base: P, server_url: &str) -> Self {
+        let host = Url::parse(server_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_else(|| "default".to_owned());
+
+        AccountStore::new(base.as_ref().join(host))
+    }
+
+    /// Returns the directory the account files are stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the account key from the store. Returns `None` if no key was saved yet.
+    pub fn load_key(&self) -> Result<Option<PKey<Private>>> {
+        let path = self.path.join(ACCOUNT_KEY_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(PKey::private_key_from_pem(&fs::read(path)?)?))
+    }
+
+    /// Loads the URL of the account from the store. Returns `None` if no account was saved yet.
+    pub fn load_account_url(&self) -> Result<Option<String>> {
+        let path = self.path.join(ACCOUNT_INFO_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let info: AccountInfo = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Some(info.account_url))
+    }
+
+    /// Saves the account key together with the account URL. The key file is only readable
+    /// by the current user.
+    pub fn save(&self, key: &PKey<Private>, account_url: &str) -> Result<()> {
+        fs::create_dir_all(&self.path)?;
+
+        write_atomic(
+            &self.path.join(ACCOUNT_KEY_FILE),
+            &key.private_key_to_pem_pkcs8()?,
+            true,
+        )?;
+
+        let info = AccountInfo {
+            account_url: account_url.to_owned(),
+        };
+        write_atomic(
+            &self.path.join(ACCOUNT_INFO_FILE),
+            serde_json::to_string_pretty(&info)?.as_bytes(),
+            false,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The MAC algorithm that's used to sign an external account binding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MacAlgorithm {
+    #[default]
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl MacAlgorithm {
+    /// Returns the name of the algorithm in the `JWS` header and its digest.
+    fn parameters(self) -> (&'static str, MessageDigest) {
+        match self {
+            MacAlgorithm::Hs256 => ("HS256", MessageDigest::sha256()),
+            MacAlgorithm::Hs384 => ("HS384", MessageDigest::sha384()),
+            MacAlgorithm::Hs512 => ("HS512", MessageDigest::sha512()),
+        }
+    }
+}
+
+/// The credentials that bind a new account to an existing account at the CA (RFC8555 section
+/// 7.3.4). Commercial CAs hand out a key identifier and a MAC key, which are needed to register.
+#[derive(Debug, Clone)]
+pub struct ExternalAccountBinding {
+    key_id: String,
+    hmac_key: Vec<u8>,
+    algorithm: MacAlgorithm,
+}
+
+impl ExternalAccountBinding {
+    /// Creates the binding from the key identifier and the `base64url` encoded MAC key, as
+    /// they are provided by the CA.
+    pub fn new(key_id: &str, hmac_key: &str, algorithm: MacAlgorithm) -> Result<Self> {
+        let hmac_key =
+            base64::decode_config(hmac_key.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+                .map_err(|_| Error::InvalidHmacKey)?;
+
+        Ok(ExternalAccountBinding {
+            key_id: key_id.to_owned(),
+            hmac_key,
+            algorithm,
+        })
+    }
+
+    /// Builds the `externalAccountBinding` field of a `newAccount` request, which is a `JWS`
+    /// over the `JWK` of the account key that's signed with the MAC key.
+    pub(crate) fn sign(&self, account_key: &PKey<Private>, url: &str) -> Result<serde_json::Value> {
+        let (alg, digest) = self.algorithm.parameters();
+        let header = json!({
+            "alg": alg,
+            "kid": self.key_id,
+            "url": url,
+        });
+
+        let header64 = b64(serde_json::to_string(&header)?.as_bytes());
+        let payload64 = b64(serde_json::to_string(&jwk(account_key)?)?.as_bytes());
+
+        let mac_key = PKey::hmac(&self.hmac_key)?;
+        let mut signer = Signer::new(digest, &mac_key)?;
+        signer.update(format!("{}.{}", header64, payload64).as_bytes())?;
+
+        Ok(json!({
+            "protected": header64,
+            "payload": payload64,
+            "signature": b64(&signer.sign_to_vec()?),
+        }))
+    }
+}
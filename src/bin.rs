@@ -1,13 +1,31 @@
+use std::io::{self, BufRead, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use acme_rs::{
-    generate_cert_for_domain,
+    account::{AccountStore, ExternalAccountBinding, MacAlgorithm},
+    change_account_key,
+    dns::{
+        acme_dns::AcmeDnsPublisher,
+        rfc2136::{Rfc2136Publisher, TsigAlgorithm},
+        ManualDnsPublisher,
+    },
+    error::Error,
+    fetch_renewal_info, generate_cert_for_domain,
+    renewal::certificate_identifier,
+    revoke_certificate,
+    solver::{ChallengeSolver, DnsSolver, HookSolver, DNS_01, HTTP_01},
     util::{
-        check_for_existing_server, generate_rsa_keypair, load_csr_from_file, load_keys_from_file,
-        save_certificates, save_keypair,
+        generate_key, load_certificate_from_file, load_csr_from_file, load_keys_from_file,
+        load_private_key_from_file, save_certificate_files,
     },
+    ChallengeType, KeyType, RevocationKey, RevocationReason, Settings,
 };
-use clap::{IntoApp, Parser};
+use clap::{ArgEnum, IntoApp, Parser, Subcommand};
 use flexi_logger::Logger;
 use log::info;
+use openssl::pkey::{PKey, Private};
 
 const LETS_ENCRYPT_SERVER: &str = "https://acme-v02.api.letsencrypt.org/directory";
 #[allow(dead_code)]
@@ -17,33 +35,267 @@ const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org
 #[derive(Parser, Debug)]
 #[clap(
     version = "0.1.0",
-    author = "Bastian Kersting <bastian@cmbt.de>, Tobias Karius <tobias.karius@yahoo.de>, Elena Lilova <elena.lilova@gmx.de>, Dominik Jantschar <dominik.jantschar@web.de>"
+    author = "Bastian Kersting <bastian@cmbt.de>, Tobias Karius <tobias.karius@yahoo.de>, Elena Lilova <elena.lilova@gmx.de>, Dominik Jantschar <dominik.jantschar@web.de>",
+    subcommand_negates_reqs = true
 )]
 struct Opts {
     // The email associated with the domain
-    #[clap(short, long)]
-    email: String,
-    /// The domain to register the certificate for
-    #[clap(short, long)]
-    domain: String,
+    #[clap(short, long, required = true)]
+    email: Option<String>,
+    /// The domain to register the certificate for. Can be repeated in order to request a
+    /// certificate that covers multiple domains
+    #[clap(short, long, required = true)]
+    domain: Vec<String>,
     /// An optional private key file (PEM format) to load the keys from
     #[clap(long)]
     private_key: Option<String>,
     // An optional public key file (PEM format) to load the keys from
     #[clap(long)]
     public_key: Option<String>,
+    /// The type of the key that's generated for the certificate if no keys are provided
+    #[clap(long, arg_enum, default_value = "rsa2048")]
+    key_type: Key,
     /// The ACME server's URL
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     server: Option<String>,
     /// Initialize a standalone web server if there is not one already using port 80.
-    #[clap(long)]
+    #[clap(long, conflicts_with = "webroot")]
     standalone: bool,
+    /// The address the standalone web server listens on, e.g. "[::]:80" for IPv6 or
+    /// "127.0.0.1:8402" behind a proxy that forwards port 80
+    #[clap(long, default_value = "0.0.0.0:80", requires = "standalone")]
+    http_address: SocketAddr,
+    /// The document root of the running web server the http-01 token is written to. Can be
+    /// given once for all domains or once for every domain, in the same order as --domain
+    #[clap(long)]
+    webroot: Vec<PathBuf>,
+    /// The challenge that's used to prove the control over the domain. The dns-01 challenge
+    /// prints the needed TXT record and waits until it was created, the tls-alpn-01 challenge
+    /// opens a TLS server on port 443. Defaults to dns-01 for wildcard domains and to http-01
+    /// otherwise.
+    #[clap(long, arg_enum)]
+    challenge: Option<Challenge>,
+    /// The directory the account key and url are stored in, so the account is reused across runs.
+    /// Every server gets its own sub directory
+    #[clap(long, default_value = "accounts", global = true)]
+    account_dir: String,
+    /// An optional account key file (PEM format) of an existing account, which isn't saved to the
+    /// account directory. If omitted, the key is loaded from the account directory or a new
+    /// account is created
+    #[clap(long, global = true)]
+    account_key: Option<String>,
+    /// The type of the key that's generated for a new account, or for the new key of a key
+    /// change if `--new-key` is omitted
+    #[clap(long, arg_enum, default_value = "rsa2048", global = true)]
+    account_key_type: Key,
+    /// Agree to the terms of service of the server. Otherwise they are shown and need to be
+    /// confirmed when a new account is registered
+    #[clap(long)]
+    agree_tos: bool,
+    /// The key identifier of the external account binding, which some CAs require for
+    /// registering a new account
+    #[clap(long, requires = "eab-hmac-key")]
+    eab_kid: Option<String>,
+    /// The base64url encoded HMAC key of the external account binding
+    #[clap(long, requires = "eab-kid")]
+    eab_hmac_key: Option<String>,
+    /// The algorithm that's used to sign the external account binding
+    #[clap(long, arg_enum, default_value = "hs256")]
+    eab_algorithm: Mac,
+    /// The primary name server that accepts dynamic updates (RFC2136) for the dns-01 challenge,
+    /// e.g. "127.0.0.1:53". The TXT records are placed without asking if it's given
+    #[clap(
+        long,
+        requires_all = &["rfc2136-zone", "rfc2136-key-name", "rfc2136-secret"]
+    )]
+    rfc2136_server: Option<SocketAddr>,
+    /// The zone the TXT records are placed in, e.g. "example.org"
+    #[clap(long, requires = "rfc2136-server")]
+    rfc2136_zone: Option<String>,
+    /// The name of the TSIG key that authenticates the dynamic updates
+    #[clap(long, requires = "rfc2136-server")]
+    rfc2136_key_name: Option<String>,
+    /// The base64 encoded secret of the TSIG key
+    #[clap(long, requires = "rfc2136-server")]
+    rfc2136_secret: Option<String>,
+    /// The algorithm of the TSIG key
+    #[clap(long, arg_enum, default_value = "hmac-sha256")]
+    rfc2136_algorithm: Tsig,
+    /// The url of an acme-dns server, to which the _acme-challenge records of the domains are
+    /// delegated with a CNAME. The CNAME is printed when a domain is registered on first use
+    #[clap(long)]
+    acme_dns_url: Option<String>,
+    /// A domain whose dns-01 challenge is completed with acme-dns, while the other domains use
+    /// the --challenge. Can be repeated, all domains use acme-dns if it's omitted
+    #[clap(long, requires = "acme-dns-url")]
+    acme_dns_domain: Vec<String>,
+    /// The file the credentials of the domains registered at acme-dns are stored in
+    #[clap(long, default_value = "acme-dns.json")]
+    acme_dns_credentials: String,
+    /// A command that completes the http-01 or dns-01 challenge of a domain, e.g. a script that
+    /// creates the TXT record. It gets the challenge in the environment variables ACME_DOMAIN,
+    /// ACME_CHALLENGE_TYPE, ACME_TOKEN, ACME_KEY_AUTHORIZATION, ACME_TXT_NAME and ACME_TXT_VALUE
+    #[clap(long, conflicts_with_all = &["standalone", "webroot", "rfc2136-server"])]
+    auth_hook: Option<String>,
+    /// A command that removes what the auth hook created, it gets the same environment variables
+    #[clap(long, requires = "auth-hook")]
+    cleanup_hook: Option<String>,
+    /// The maximum number of seconds to wait for the server to validate the challenges and
+    /// to issue the certificate
+    #[clap(long, default_value = "120")]
+    timeout: u64,
+    /// The PEM formatted certificate that's renewed by this request. It's reported to the server,
+    /// which may exempt the renewal from rate limits
+    #[clap(long)]
+    replaces: Option<String>,
+    /// The common name of the root whose certificate chain is preferred if the server offers
+    /// alternate chains, e.g. "ISRG Root X1"
+    #[clap(long)]
+    preferred_chain: Option<String>,
+    /// The directory the certificate and its key are stored in. Every certificate gets its own
+    /// sub directory that's named after the first domain
+    #[clap(long, default_value = "certs")]
+    output_dir: String,
     /// An optional path to a PEM formatted Certificate Signing Request (CSR)
     #[clap(long)]
     csr_path: Option<String>,
     /// Enables debug output.
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     verbose: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// The commands besides requesting a certificate.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Revokes a certificate. The request is signed with the account key, or with the key of the
+    /// certificate if --certificate-key is given
+    Revoke {
+        /// The PEM formatted certificate to revoke
+        certificate: String,
+        /// The reason for the revocation
+        #[clap(long, arg_enum, default_value = "unspecified")]
+        reason: Reason,
+        /// The private key of the certificate (PEM format)
+        #[clap(long)]
+        certificate_key: Option<String>,
+    },
+    /// Rolls the account over to a new key, which is saved in the account directory once the
+    /// server confirmed the change
+    KeyChange {
+        /// The new account key (PEM format). If omitted, a new key is generated, which requires
+        /// the account key to be loaded from the account directory
+        #[clap(long)]
+        new_key: Option<String>,
+    },
+    /// Shows the renewal information (ARI) of a certificate, i.e. the window in which the CA
+    /// wants the certificate to be renewed
+    RenewalInfo {
+        /// The PEM formatted certificate
+        certificate: String,
+    },
+}
+
+/// The challenges that can be selected from the command line.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum Challenge {
+    #[clap(name = "http-01")]
+    Http01,
+    #[clap(name = "dns-01")]
+    Dns01,
+    #[clap(name = "tls-alpn-01")]
+    TlsAlpn01,
+}
+
+/// The key types that can be selected from the command line.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum Key {
+    Rsa2048,
+    Rsa3072,
+    Rsa4096,
+    EcP256,
+    EcP384,
+}
+
+impl From<Key> for KeyType {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Rsa2048 => KeyType::Rsa2048,
+            Key::Rsa3072 => KeyType::Rsa3072,
+            Key::Rsa4096 => KeyType::Rsa4096,
+            Key::EcP256 => KeyType::EcP256,
+            Key::EcP384 => KeyType::EcP384,
+        }
+    }
+}
+
+/// The MAC algorithms of the external account binding that can be selected from the command line.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum Mac {
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl From<Mac> for MacAlgorithm {
+    fn from(mac: Mac) -> Self {
+        match mac {
+            Mac::Hs256 => MacAlgorithm::Hs256,
+            Mac::Hs384 => MacAlgorithm::Hs384,
+            Mac::Hs512 => MacAlgorithm::Hs512,
+        }
+    }
+}
+
+/// The TSIG algorithms of the dynamic dns updates that can be selected from the command line.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum Tsig {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl From<Tsig> for TsigAlgorithm {
+    fn from(tsig: Tsig) -> Self {
+        match tsig {
+            Tsig::HmacSha256 => TsigAlgorithm::HmacSha256,
+            Tsig::HmacSha384 => TsigAlgorithm::HmacSha384,
+            Tsig::HmacSha512 => TsigAlgorithm::HmacSha512,
+        }
+    }
+}
+
+/// The revocation reasons that can be selected from the command line.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum Reason {
+    Unspecified,
+    KeyCompromise,
+    CaCompromise,
+    AffiliationChanged,
+    Superseded,
+    CessationOfOperation,
+    CertificateHold,
+    RemoveFromCrl,
+    PrivilegeWithdrawn,
+    AaCompromise,
+}
+
+impl From<Reason> for RevocationReason {
+    fn from(reason: Reason) -> Self {
+        match reason {
+            Reason::Unspecified => RevocationReason::Unspecified,
+            Reason::KeyCompromise => RevocationReason::KeyCompromise,
+            Reason::CaCompromise => RevocationReason::CaCompromise,
+            Reason::AffiliationChanged => RevocationReason::AffiliationChanged,
+            Reason::Superseded => RevocationReason::Superseded,
+            Reason::CessationOfOperation => RevocationReason::CessationOfOperation,
+            Reason::CertificateHold => RevocationReason::CertificateHold,
+            Reason::RemoveFromCrl => RevocationReason::RemoveFromCrl,
+            Reason::PrivilegeWithdrawn => RevocationReason::PrivilegeWithdrawn,
+            Reason::AaCompromise => RevocationReason::AaCompromise,
+        }
+    }
 }
 
 fn main() {
@@ -59,6 +311,37 @@ fn main() {
             .unwrap_or_else(|e| panic!("Logger initialization failed with {}", e));
     }
 
+    let server = opts
+        .server
+        .clone()
+        .unwrap_or_else(|| LETS_ENCRYPT_SERVER.to_owned());
+
+    match &opts.command {
+        Some(Command::Revoke {
+            certificate,
+            reason,
+            certificate_key,
+        }) => {
+            revoke(
+                &opts,
+                &server,
+                certificate,
+                *reason,
+                certificate_key.as_deref(),
+            );
+            return;
+        }
+        Some(Command::KeyChange { new_key }) => {
+            key_change(&opts, &server, new_key.as_deref());
+            return;
+        }
+        Some(Command::RenewalInfo { certificate }) => {
+            renewal_info(&server, certificate);
+            return;
+        }
+        None => {}
+    }
+
     if opts.csr_path.is_some() && (opts.private_key.is_none() || opts.public_key.is_none()) {
         app.error(
             clap::ErrorKind::ArgumentConflict,
@@ -68,8 +351,8 @@ fn main() {
         .exit();
     }
 
-    // create a new key pair or otherwise read from a file
-    let keypair_for_cert = match (opts.private_key.as_ref(), opts.public_key.as_ref()) {
+    // create a new key or otherwise read it from a file
+    let key_for_cert = match (opts.private_key.as_ref(), opts.public_key.as_ref()) {
         (Some(priv_path), Some(pub_path)) => load_keys_from_file(priv_path, pub_path),
         (Some(_), None) | (None, Some(_)) => app
             .error(
@@ -78,52 +361,335 @@ fn main() {
             )
             .exit(),
 
-        (None, None) => generate_rsa_keypair(),
+        (None, None) => generate_key(opts.key_type.into()),
     }
-    .expect("Could not generate keypair");
+    .expect("Could not generate key");
 
-    let optional_csr = opts
-        .csr_path
-        .map(|path| load_csr_from_file(&path).expect("Error loading the CSR"));
+    let csr_path = &opts.csr_path;
+    let load_csr = || {
+        csr_path
+            .as_ref()
+            .map(|path| load_csr_from_file(path).expect("Error loading the CSR"))
+    };
+    let optional_csr = load_csr();
 
     if opts.verbose && optional_csr.is_some() {
         info!("Successfully loaded CSR");
     }
 
-    if opts.standalone && check_for_existing_server() {
+    if let Some(domain) = opts
+        .acme_dns_domain
+        .iter()
+        .find(|domain| !opts.domain.contains(domain))
+    {
+        app.error(
+            clap::ErrorKind::InvalidValue,
+            format!(
+                "Error! The acme-dns domain {} isn't one of the domains",
+                domain
+            ),
+        )
+        .exit();
+    }
+
+    // the domains whose challenge is completed with acme-dns
+    let acme_dns_domains = match (&opts.acme_dns_url, opts.acme_dns_domain.is_empty()) {
+        (None, _) => Vec::new(),
+        (Some(_), true) => opts.domain.clone(),
+        (Some(_), false) => opts.acme_dns_domain.clone(),
+    };
+
+    // wildcard domains can only be validated with the dns challenge
+    let has_wildcard = opts
+        .domain
+        .iter()
+        .any(|domain| domain.starts_with("*.") && !acme_dns_domains.contains(domain));
+    let challenge = match opts.challenge {
+        Some(challenge) => challenge,
+        None if has_wildcard => Challenge::Dns01,
+        None => Challenge::Http01,
+    };
+
+    if has_wildcard && !matches!(challenge, Challenge::Dns01) {
+        app.error(
+            clap::ErrorKind::ArgumentConflict,
+            "Error! Wildcard domains can only be validated with the dns-01 challenge",
+        )
+        .exit();
+    }
+
+    if opts.standalone && !matches!(challenge, Challenge::Http01) {
+        app.error(
+            clap::ErrorKind::ArgumentConflict,
+            "Error! The standalone option can only be used with the http-01 challenge",
+        )
+        .exit();
+    }
+
+    if !opts.webroot.is_empty() && !matches!(challenge, Challenge::Http01) {
+        app.error(
+            clap::ErrorKind::ArgumentConflict,
+            "Error! The webroot option can only be used with the http-01 challenge",
+        )
+        .exit();
+    }
+
+    if opts.webroot.len() > 1 && opts.webroot.len() != opts.domain.len() {
+        app.error(
+            clap::ErrorKind::WrongNumberOfValues,
+            "Error! Provide either a single webroot or one for every domain",
+        )
+        .exit();
+    }
+
+    if opts.standalone && TcpStream::connect(opts.http_address).is_ok() {
         app.error(
             clap::ErrorKind::DisplayHelp,
-            "Error! Provided the standalone option with a process already listening on port 80",
+            format!(
+                "Error! Provided the standalone option with a process already listening on {}",
+                opts.http_address
+            ),
+        )
+        .exit();
+    }
+
+    if opts.rfc2136_server.is_some() && !matches!(challenge, Challenge::Dns01) {
+        app.error(
+            clap::ErrorKind::ArgumentConflict,
+            "Error! Dynamic dns updates can only be used with the dns-01 challenge",
         )
         .exit();
     }
 
-    // get the certificate
-    let cert_chain = match opts.server {
-        Some(url) => generate_cert_for_domain(
-            &keypair_for_cert,
-            optional_csr,
-            opts.domain,
-            url,
-            opts.email,
-            opts.standalone,
-            opts.verbose,
+    let rfc2136_publisher = match (
+        opts.rfc2136_server,
+        &opts.rfc2136_zone,
+        &opts.rfc2136_key_name,
+        &opts.rfc2136_secret,
+    ) {
+        (Some(server), Some(zone), Some(key_name), Some(secret)) => Some(
+            Rfc2136Publisher::new(
+                server,
+                zone,
+                key_name,
+                opts.rfc2136_algorithm.into(),
+                secret,
+            )
+            .expect("Error loading the TSIG key"),
         ),
-        None => generate_cert_for_domain(
-            &keypair_for_cert,
-            optional_csr,
-            opts.domain,
-            LETS_ENCRYPT_SERVER.to_owned(),
-            opts.email,
-            opts.standalone,
-            opts.verbose,
+        _ => None,
+    };
+
+    let acme_dns_publisher = opts
+        .acme_dns_url
+        .as_ref()
+        .map(|url| AcmeDnsPublisher::new(url, &opts.acme_dns_credentials));
+    let acme_dns_solver = acme_dns_publisher
+        .as_ref()
+        .map(|publisher| DnsSolver::new(publisher));
+
+    let hook_solver = opts.auth_hook.as_ref().map(|auth_hook| {
+        let challenge_type = match challenge {
+            Challenge::Http01 => HTTP_01,
+            Challenge::Dns01 => DNS_01,
+            Challenge::TlsAlpn01 => app
+                .error(
+                    clap::ErrorKind::ArgumentConflict,
+                    "Error! Hooks can only be used with the http-01 and dns-01 challenge",
+                )
+                .exit(),
+        };
+        HookSolver::new(challenge_type, auth_hook, opts.cleanup_hook.as_deref())
+    });
+
+    let challenge_type = match (&hook_solver, challenge) {
+        (Some(solver), _) => ChallengeType::Custom(solver),
+        (None, Challenge::Http01) => ChallengeType::Http {
+            standalone: opts.standalone,
+        },
+        (None, Challenge::Dns01) => match &rfc2136_publisher {
+            Some(publisher) => ChallengeType::Dns(publisher),
+            None => ChallengeType::Dns(&ManualDnsPublisher),
+        },
+        (None, Challenge::TlsAlpn01) => ChallengeType::TlsAlpn,
+    };
+
+    let account_key = opts
+        .account_key
+        .map(|path| load_private_key_from_file(&path).expect("Error loading the account key"));
+
+    let external_account_binding = match (&opts.eab_kid, &opts.eab_hmac_key) {
+        (Some(kid), Some(hmac_key)) => Some(
+            ExternalAccountBinding::new(kid, hmac_key, opts.eab_algorithm.into())
+                .expect("Error loading the external account binding"),
         ),
+        _ => None,
+    };
+
+    let email = opts.email.clone().unwrap_or_default();
+    let mut settings = Settings::new(&opts.domain, &server, &email);
+    settings.account_key = account_key;
+    settings.account_key_type = opts.account_key_type.into();
+    settings.account_store = Some(AccountStore::for_server(&opts.account_dir, &server));
+    settings.external_account_binding = external_account_binding;
+    settings.terms_of_service_agreed = opts.agree_tos;
+    settings.preferred_chain = opts.preferred_chain.clone();
+    settings.replaces = opts.replaces.as_ref().map(|path| {
+        load_certificate_from_file(path)
+            .and_then(|certificate| certificate_identifier(&certificate))
+            .expect("Error loading the replaced certificate")
+    });
+    settings.challenge_type = challenge_type;
+    if let Some(solver) = &acme_dns_solver {
+        for domain in &acme_dns_domains {
+            let domain = domain.strip_prefix("*.").unwrap_or(domain);
+            settings
+                .domain_solvers
+                .insert(domain.to_owned(), solver as &dyn ChallengeSolver);
+        }
+    }
+    settings.webroots = opts.webroot.clone();
+    settings.standalone_address = opts.http_address;
+    settings.timeout = Duration::from_secs(opts.timeout);
+    settings.verbose = opts.verbose;
+
+    // get the certificate, the terms of service are confirmed interactively if a new account
+    // is registered without --agree-tos
+    let cert_chain = match generate_cert_for_domain(&key_for_cert, optional_csr, &settings) {
+        Err(Error::TermsOfServiceNotAgreed(url)) if confirm_terms_of_service(&url) => {
+            settings.terms_of_service_agreed = true;
+            generate_cert_for_domain(&key_for_cert, load_csr(), &settings)
+        }
+        result => result,
     }
     .expect("Error during creation");
 
-    // save the certificate and the keypair
-    save_certificates(cert_chain).expect("Unable to save certificate");
-    if opts.public_key.as_ref().is_none() {
-        save_keypair(&keypair_for_cert).expect("Unable to save keypair");
+    // save the certificate and its key
+    let directory = save_certificate_files(
+        Path::new(&opts.output_dir),
+        &opts.domain[0],
+        &cert_chain,
+        &key_for_cert,
+    )
+    .expect("Unable to save certificate");
+    info!("Saved the certificate to {}", directory.display());
+}
+
+/// Shows the url of the terms of service and asks whether the user agrees to them.
+fn confirm_terms_of_service(url: &str) -> bool {
+    print!("Do you agree to the terms of service at {}? [y/N] ", url);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+/// Revokes the certificate with either the key of the certificate or the account key, which is
+/// loaded from the --account-key file or the account directory.
+fn revoke(
+    opts: &Opts,
+    server: &str,
+    certificate_path: &str,
+    reason: Reason,
+    certificate_key_path: Option<&str>,
+) {
+    let certificate =
+        load_certificate_from_file(certificate_path).expect("Error loading the certificate");
+
+    let key = match certificate_key_path {
+        Some(path) => load_private_key_from_file(path).expect("Error loading the key"),
+        None => load_account_key(opts, server),
+    };
+
+    let revocation_key = match certificate_key_path {
+        Some(_) => RevocationKey::Certificate(&key),
+        None => RevocationKey::Account(&key),
+    };
+
+    revoke_certificate(server, &certificate, reason.into(), revocation_key)
+        .expect("Error while revoking the certificate");
+
+    if opts.verbose {
+        info!("Revoked the certificate {}", certificate_path);
+    }
+}
+
+/// Prints the window in which the certificate should be renewed.
+fn renewal_info(server: &str, certificate_path: &str) {
+    let certificate =
+        load_certificate_from_file(certificate_path).expect("Error loading the certificate");
+
+    let info = match fetch_renewal_info(server, &certificate)
+        .expect("Error while fetching the renewal information")
+    {
+        Some(info) => info,
+        None => {
+            println!("The server doesn't provide renewal information");
+            return;
+        }
+    };
+
+    println!(
+        "Suggested renewal window: {} - {}",
+        httpdate::fmt_http_date(info.window_start),
+        httpdate::fmt_http_date(info.window_end)
+    );
+    if let Some(url) = &info.explanation_url {
+        println!("Explanation: {}", url);
+    }
+    if info.should_renew() {
+        println!("The certificate should be renewed now");
+    }
+}
+
+/// Rolls the account over to a new key, which is either loaded from a file or generated.
+fn key_change(opts: &Opts, server: &str, new_key_path: Option<&str>) {
+    // a generated key is only saved to the account directory, which may hold a different
+    // account than the one of --account-key
+    if new_key_path.is_none() && opts.account_key.is_some() {
+        panic!("The new key needs to be passed via --new-key if --account-key is given");
+    }
+
+    let old_key = load_account_key(opts, server);
+    let new_key = match new_key_path {
+        Some(path) => load_private_key_from_file(path),
+        None => generate_key(opts.account_key_type.into()),
+    }
+    .expect("Error loading the new account key");
+
+    let store = AccountStore::for_server(&opts.account_dir, server);
+    change_account_key(server, &old_key, &new_key, Some(&store))
+        .expect("Error while changing the account key");
+
+    if opts.verbose {
+        let saved = store
+            .load_key()
+            .ok()
+            .flatten()
+            .is_some_and(|key| key.public_eq(&new_key));
+        if saved {
+            info!(
+                "Changed the account key, the new key is saved in {}",
+                store.path().display()
+            );
+        } else {
+            info!("Changed the account key, the account directory holds a different account");
+        }
+    }
+}
+
+/// Loads the account key from the --account-key file or the account directory.
+fn load_account_key(opts: &Opts, server: &str) -> PKey<Private> {
+    match &opts.account_key {
+        Some(path) => load_private_key_from_file(path),
+        None => AccountStore::for_server(&opts.account_dir, server)
+            .load_key()
+            .map(|key| key.expect("No account key found, provide one via --account-key")),
     }
+    .expect("Error loading the account key")
 }
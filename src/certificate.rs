@@ -0,0 +1,146 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use openssl::{
+    asn1::{Asn1Time, Asn1TimeRef},
+    hash::MessageDigest,
+    nid::Nid,
+    x509::{X509NameRef, X509},
+};
+
+use crate::error::{Error, Result};
+
+/// A certificate chain that was issued by the server. The first certificate is the one issued
+/// for the domains (leaf), it's followed by the intermediate certificates that lead to the root.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    chain: Vec<X509>,
+}
+
+impl Certificate {
+    /// Parses a PEM formatted certificate chain as it's returned by the server.
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let chain = X509::stack_from_pem(pem)?;
+        if chain.is_empty() {
+            return Err(Error::EmptyCertificateChain);
+        }
+
+        Ok(Certificate { chain })
+    }
+
+    /// Returns the certificate that was issued for the domains.
+    pub fn leaf(&self) -> &X509 {
+        &self.chain[0]
+    }
+
+    /// Returns the intermediate certificates of the chain.
+    pub fn intermediates(&self) -> &[X509] {
+        &self.chain[1..]
+    }
+
+    /// Returns the whole chain, starting with the leaf certificate.
+    pub fn chain(&self) -> &[X509] {
+        &self.chain
+    }
+
+    /// Returns the time from which on the leaf certificate is valid.
+    pub fn not_before(&self) -> Result<SystemTime> {
+        to_system_time(self.leaf().not_before())
+    }
+
+    /// Returns the time at which the leaf certificate expires.
+    pub fn not_after(&self) -> Result<SystemTime> {
+        to_system_time(self.leaf().not_after())
+    }
+
+    /// Returns the dns names in the subject alternative names of the leaf certificate.
+    pub fn subject_alt_names(&self) -> Vec<String> {
+        self.leaf()
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| name.dnsname().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the serial number of the leaf certificate as upper case hex string.
+    pub fn serial(&self) -> Result<String> {
+        Ok(self
+            .leaf()
+            .serial_number()
+            .to_bn()?
+            .to_hex_str()?
+            .to_string())
+    }
+
+    /// Returns the common name of the issuer of the leaf certificate.
+    pub fn issuer(&self) -> Option<String> {
+        common_name(self.leaf().issuer_name())
+    }
+
+    /// Returns the `SHA-256` fingerprint of the leaf certificate as colon separated hex string,
+    /// e.g. `AB:CD:...`.
+    pub fn fingerprint(&self) -> Result<String> {
+        Ok(self
+            .leaf()
+            .digest(MessageDigest::sha256())?
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":"))
+    }
+
+    /// Returns the common name of the root the chain leads to, which is the issuer of the topmost
+    /// certificate. If the chain includes the self-signed root, its issuer is the root itself.
+    pub fn root_issuer(&self) -> Option<String> {
+        self.chain
+            .last()
+            .and_then(|certificate| common_name(certificate.issuer_name()))
+    }
+
+    /// Encodes the leaf certificate in the PEM format.
+    pub fn leaf_to_pem(&self) -> Result<Vec<u8>> {
+        Ok(self.leaf().to_pem()?)
+    }
+
+    /// Encodes the intermediate certificates in the PEM format.
+    pub fn intermediates_to_pem(&self) -> Result<Vec<u8>> {
+        to_pem(self.intermediates())
+    }
+
+    /// Encodes the whole chain in the PEM format.
+    pub fn to_pem(&self) -> Result<Vec<u8>> {
+        to_pem(&self.chain)
+    }
+}
+
+/// Encodes a list of certificates in the PEM format.
+fn to_pem(certificates: &[X509]) -> Result<Vec<u8>> {
+    let mut pem = Vec::new();
+    for certificate in certificates {
+        pem.extend(certificate.to_pem()?);
+    }
+
+    Ok(pem)
+}
+
+/// Returns the first common name of a x509 name.
+fn common_name(name: &X509NameRef) -> Option<String> {
+    name.entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .and_then(|entry| entry.data().to_string().ok())
+}
+
+/// Converts an `ASN.1` time to a `SystemTime`.
+fn to_system_time(time: &Asn1TimeRef) -> Result<SystemTime> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    let seconds = i64::from(diff.days) * 86400 + i64::from(diff.secs);
+
+    Ok(if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    })
+}
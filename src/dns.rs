@@ -0,0 +1,78 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use openssl::sha::sha256;
+
+use crate::{error::Result, util::b64};
+
+/// Places the `TXT` records at an acme-dns server, to which the records are delegated.
+pub mod acme_dns;
+/// Places the `TXT` records with dynamic updates (RFC2136), e.g. on BIND or Knot.
+pub mod rfc2136;
+
+/// The label that's prepended to the domain in order to build the name of the `TXT` record.
+pub const ACME_CHALLENGE_LABEL: &str = "_acme-challenge";
+
+/// The time that's waited by default for a published record to become visible.
+const DEFAULT_PROPAGATION_DELAY: Duration = Duration::from_secs(30);
+
+/// A publisher that is able to place the `TXT` record needed for the dns-01 challenge in
+/// the zone of a domain. Implement this trait in order to connect `acme-rs` to the
+/// DNS provider of your choice.
+pub trait DnsPublisher {
+    /// Creates a `TXT` record with the given name and value.
+    fn publish(&self, record_name: &str, value: &str) -> Result<()>;
+
+    /// Blocks until the published record is visible to the `ACME` server. The default
+    /// implementation just waits for a fixed amount of time.
+    fn wait_for_propagation(&self, _record_name: &str, _value: &str) -> Result<()> {
+        std::thread::sleep(DEFAULT_PROPAGATION_DELAY);
+        Ok(())
+    }
+
+    /// Removes the record again after the challenge was completed.
+    fn remove(&self, _record_name: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A `DnsPublisher` that prints the record to stdout and waits for the user to
+/// create it manually.
+#[derive(Debug, Default)]
+pub struct ManualDnsPublisher;
+
+impl DnsPublisher for ManualDnsPublisher {
+    fn publish(&self, record_name: &str, value: &str) -> Result<()> {
+        println!("Please create the following DNS record:");
+        println!();
+        println!("    {}. IN TXT \"{}\"", record_name, value);
+        println!();
+        print!("Press enter once the record is visible to the public... ");
+        io::stdout().flush()?;
+
+        io::stdin().lock().read_line(&mut String::new())?;
+
+        Ok(())
+    }
+
+    fn wait_for_propagation(&self, _record_name: &str, _value: &str) -> Result<()> {
+        // the user already confirmed that the record is visible
+        Ok(())
+    }
+
+    fn remove(&self, record_name: &str, _value: &str) -> Result<()> {
+        println!("The TXT record {} can be removed now.", record_name);
+        Ok(())
+    }
+}
+
+/// Returns the name of the `TXT` record for a certain domain, e.g. `_acme-challenge.example.org`.
+pub fn record_name(domain: &str) -> String {
+    format!("{}.{}", ACME_CHALLENGE_LABEL, domain)
+}
+
+/// Computes the value of the `TXT` record for a key authorization, which is the
+/// `base64url` encoded `SHA-256` digest of it (RFC8555 section 8.4).
+pub fn txt_record_value(key_authorization: &str) -> String {
+    b64(&sha256(key_authorization.as_bytes()))
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    dns::{DnsPublisher, ACME_CHALLENGE_LABEL},
+    error::Result,
+    util::write_atomic,
+};
+
+/// The credentials of a domain at the acme-dns server, as they are returned by `/register`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AcmeDnsAccount {
+    username: String,
+    password: String,
+    fulldomain: String,
+    subdomain: String,
+}
+
+/// A `DnsPublisher` that places the `TXT` records at an [acme-dns](https://github.com/joohoi/acme-dns)
+/// server, to which the `_acme-challenge` records of the domains are delegated with a `CNAME`.
+/// Every domain is registered at the server on first use and its credentials are stored in a
+/// file, which is only readable by the current user. The `CNAME` that needs to be created is
+/// printed once the domain was registered.
+///
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{dns::acme_dns::AcmeDnsPublisher, solver::DnsSolver};
+///
+/// let publisher = AcmeDnsPublisher::new("https://auth.example.org", "acme-dns.json");
+/// let solver = DnsSolver::new(&publisher);
+/// settings.domain_solvers.insert("example.org".to_owned(), &solver);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AcmeDnsPublisher {
+    client: Client,
+    server: String,
+    credentials_path: PathBuf,
+}
+
+impl AcmeDnsPublisher {
+    /// Creates a publisher for the acme-dns server at the url, which stores the credentials
+    /// of the domains in the file.
+    pub fn new<P: Into<PathBuf>>(server: &str, credentials_path: P) -> Self {
+        AcmeDnsPublisher {
+            client: Client::new(),
+            server: server.trim_end_matches('/').to_owned(),
+            credentials_path: credentials_path.into(),
+        }
+    }
+
+    /// Loads the stored credentials of all domains.
+    fn load_accounts(&self) -> Result<BTreeMap<String, AcmeDnsAccount>> {
+        if !self.credentials_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        Ok(serde_json::from_slice(&fs::read(&self.credentials_path)?)?)
+    }
+
+    /// Returns the credentials of the domain, which is registered at the server if it wasn't
+    /// before.
+    fn account(&self, domain: &str) -> Result<AcmeDnsAccount> {
+        let mut accounts = self.load_accounts()?;
+        if let Some(account) = accounts.get(domain) {
+            return Ok(account.clone());
+        }
+
+        let account: AcmeDnsAccount = self
+            .client
+            .post(format!("{}/register", self.server))
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        accounts.insert(domain.to_owned(), account.clone());
+        if let Some(directory) = self.credentials_path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        write_atomic(
+            &self.credentials_path,
+            serde_json::to_string_pretty(&accounts)?.as_bytes(),
+            true,
+        )?;
+
+        // the challenge can only be validated once the record is delegated to acme-dns
+        println!("Registered {} at the acme-dns server.", domain);
+        println!("Please create the following DNS record:");
+        println!();
+        println!(
+            "    {}.{}. IN CNAME {}.",
+            ACME_CHALLENGE_LABEL, domain, account.fulldomain
+        );
+        println!();
+        print!("Press enter once the record is visible to the public... ");
+        io::stdout().flush()?;
+
+        io::stdin().lock().read_line(&mut String::new())?;
+
+        Ok(account)
+    }
+}
+
+impl DnsPublisher for AcmeDnsPublisher {
+    fn publish(&self, record_name: &str, value: &str) -> Result<()> {
+        let domain = record_name
+            .strip_prefix(ACME_CHALLENGE_LABEL)
+            .and_then(|domain| domain.strip_prefix('.'))
+            .unwrap_or(record_name);
+        let account = self.account(domain)?;
+
+        self.client
+            .post(format!("{}/update", self.server))
+            .header("X-Api-User", &account.username)
+            .header("X-Api-Key", &account.password)
+            .json(&json!({
+                "subdomain": account.subdomain,
+                "txt": value,
+            }))
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    // acme-dns keeps the two most recent records of a domain and has no way to remove
+    // them, so the default implementation of `remove` is kept
+}
@@ -0,0 +1,636 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, rand::rand_bytes, sign::Signer};
+
+use crate::{
+    dns::DnsPublisher,
+    error::{Error, Result},
+};
+
+/// The opcode of an `UPDATE` message (RFC2136 section 1.3) in the flags of the header.
+const OPCODE_UPDATE: u16 = 5 << 11;
+/// The flag that marks a message as response.
+const FLAG_RESPONSE: u16 = 1 << 15;
+const TYPE_SOA: u16 = 6;
+const TYPE_TXT: u16 = 16;
+const TYPE_TSIG: u16 = 250;
+const CLASS_IN: u16 = 1;
+/// The class of a record that's deleted (RFC2136 section 2.5.4).
+const CLASS_NONE: u16 = 254;
+const CLASS_ANY: u16 = 255;
+/// The time to live of the created `TXT` records, which are only needed for the validation.
+const RECORD_TTL: u32 = 60;
+/// The number of seconds the clocks of client and server may differ (RFC8945 section 5.2.3).
+const TSIG_FUDGE: u16 = 300;
+/// The time after which the connection to the server is given up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The algorithm that's used to sign the updates with the shared secret.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TsigAlgorithm {
+    #[default]
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// Returns the name of the algorithm in the `TSIG` record and its digest.
+    fn parameters(self) -> (&'static str, MessageDigest) {
+        match self {
+            TsigAlgorithm::HmacSha256 => ("hmac-sha256", MessageDigest::sha256()),
+            TsigAlgorithm::HmacSha384 => ("hmac-sha384", MessageDigest::sha384()),
+            TsigAlgorithm::HmacSha512 => ("hmac-sha512", MessageDigest::sha512()),
+        }
+    }
+}
+
+/// A `DnsPublisher` that places the `TXT` records with dynamic updates (RFC2136), which are
+/// authenticated with a shared secret (`TSIG`, RFC8945). This is supported by most authoritative
+/// name servers like BIND or Knot.
+///
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{dns::rfc2136::{Rfc2136Publisher, TsigAlgorithm}, ChallengeType};
+///
+/// let publisher = Rfc2136Publisher::new(
+///     "127.0.0.1:53".parse().unwrap(),
+///     "example.org",
+///     "acme-key",
+///     TsigAlgorithm::HmacSha256,
+///     "c2VjcmV0IGtleSBmb3IgdGhlIGFjbWUgdXBkYXRlcw==",
+/// )
+/// .expect("Invalid TSIG secret");
+/// settings.challenge_type = ChallengeType::Dns(&publisher);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rfc2136Publisher {
+    server: SocketAddr,
+    zone: String,
+    key_name: String,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+impl Rfc2136Publisher {
+    /// Creates a publisher that sends the updates for the zone to the primary name server. The
+    /// secret is base64 encoded, as it's written to the key files of BIND and Knot.
+    pub fn new(
+        server: SocketAddr,
+        zone: &str,
+        key_name: &str,
+        algorithm: TsigAlgorithm,
+        secret: &str,
+    ) -> Result<Self> {
+        let secret = base64::decode(secret.trim()).map_err(|_| Error::InvalidTsigSecret)?;
+
+        Ok(Rfc2136Publisher {
+            server,
+            zone: zone.to_owned(),
+            key_name: key_name.to_owned(),
+            algorithm,
+            secret,
+        })
+    }
+
+    /// Sends an update that adds or deletes (`CLASS_NONE`) the `TXT` record.
+    fn update(&self, record_name: &str, value: &str, class: u16, ttl: u32) -> Result<()> {
+        let mut id = [0; 2];
+        rand_bytes(&mut id)?;
+        let id = u16::from_be_bytes(id);
+
+        // header with a single zone and update entry
+        let mut message = Vec::new();
+        for field in [id, OPCODE_UPDATE, 1, 0, 1, 0] {
+            message.extend_from_slice(&field.to_be_bytes());
+        }
+
+        // zone section
+        message.extend(encode_name(&self.zone)?);
+        message.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        // update section
+        let rdata = txt_rdata(value);
+        message.extend(encode_name(record_name)?);
+        message.extend_from_slice(&TYPE_TXT.to_be_bytes());
+        message.extend_from_slice(&class.to_be_bytes());
+        message.extend_from_slice(&ttl.to_be_bytes());
+        message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        message.extend(rdata);
+
+        let request_mac = self.sign(&mut message, id)?;
+        let response = self.send(&message)?;
+
+        self.check_response(&response, id, &request_mac)
+    }
+
+    /// Appends the `TSIG` record to the message and returns its `MAC`.
+    fn sign(&self, message: &mut Vec<u8>, id: u16) -> Result<Vec<u8>> {
+        let (algorithm, _) = self.algorithm.parameters();
+        let time_signed = unix_time();
+
+        let variables = self.tsig_variables(time_signed, TSIG_FUDGE, 0, &[])?;
+        let mac = self.mac(&[message, &variables])?;
+
+        let mut rdata = encode_name(algorithm)?;
+        rdata.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        rdata.extend_from_slice(&TSIG_FUDGE.to_be_bytes());
+        rdata.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&mac);
+        // original id, error and the length of the other data
+        for field in [id, 0, 0] {
+            rdata.extend_from_slice(&field.to_be_bytes());
+        }
+
+        message.extend(encode_name(&self.key_name)?);
+        message.extend_from_slice(&TYPE_TSIG.to_be_bytes());
+        message.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        message.extend_from_slice(&0u32.to_be_bytes());
+        message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        message.extend(rdata);
+        // the TSIG record is the only additional record
+        message[10..12].copy_from_slice(&1u16.to_be_bytes());
+
+        Ok(mac)
+    }
+
+    /// Returns the `TSIG` variables that are signed together with the message
+    /// (RFC8945 section 4.3.3).
+    fn tsig_variables(
+        &self,
+        time_signed: u64,
+        fudge: u16,
+        error: u16,
+        other_data: &[u8],
+    ) -> Result<Vec<u8>> {
+        let (algorithm, _) = self.algorithm.parameters();
+
+        let mut variables = encode_name(&self.key_name)?;
+        variables.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        variables.extend_from_slice(&0u32.to_be_bytes());
+        variables.extend(encode_name(algorithm)?);
+        variables.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        variables.extend_from_slice(&fudge.to_be_bytes());
+        variables.extend_from_slice(&error.to_be_bytes());
+        variables.extend_from_slice(&(other_data.len() as u16).to_be_bytes());
+        variables.extend_from_slice(other_data);
+
+        Ok(variables)
+    }
+
+    /// Computes the `HMAC` over the parts with the shared secret.
+    fn mac(&self, parts: &[&[u8]]) -> Result<Vec<u8>> {
+        let (_, digest) = self.algorithm.parameters();
+        let key = PKey::hmac(&self.secret)?;
+        let mut signer = Signer::new(digest, &key)?;
+        for part in parts {
+            signer.update(part)?;
+        }
+
+        Ok(signer.sign_to_vec()?)
+    }
+
+    /// Sends the message over `TCP`, which is supported by every server and avoids truncated
+    /// responses, and returns the response.
+    fn send(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = TcpStream::connect_timeout(&self.server, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        stream.write_all(&(message.len() as u16).to_be_bytes())?;
+        stream.write_all(message)?;
+
+        let mut length = [0; 2];
+        stream.read_exact(&mut length)?;
+        let mut response = vec![0; u16::from_be_bytes(length) as usize];
+        stream.read_exact(&mut response)?;
+
+        Ok(response)
+    }
+
+    /// Verifies the `TSIG` record of the response and checks whether the update succeeded.
+    fn check_response(&self, response: &[u8], id: u16, request_mac: &[u8]) -> Result<()> {
+        let mut reader = Reader::new(response);
+        let response_id = reader.u16()?;
+        let flags = reader.u16()?;
+        let counts = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+        if response_id != id || flags & FLAG_RESPONSE == 0 {
+            return Err(Error::InvalidDnsResponse);
+        }
+        let rcode = flags & 0x0f;
+
+        // servers don't sign responses to requests whose key they don't know
+        if counts[3] == 0 {
+            return match rcode {
+                0 => Err(Error::InvalidTsigSignature),
+                _ => Err(Error::DnsUpdateRejected(rcode_name(rcode))),
+            };
+        }
+
+        // skip to the TSIG record, which is always the last additional record
+        for _ in 0..counts[0] {
+            reader.skip_name()?;
+            reader.bytes(4)?;
+        }
+        for _ in 0..(counts[1] as usize + counts[2] as usize + counts[3] as usize - 1) {
+            reader.skip_record()?;
+        }
+        let tsig_start = reader.position;
+
+        reader.skip_name()?;
+        if reader.u16()? != TYPE_TSIG {
+            return Err(Error::InvalidTsigSignature);
+        }
+        reader.bytes(8)?;
+        reader.skip_name()?;
+        let time_signed = reader
+            .bytes(6)?
+            .iter()
+            .fold(0u64, |time, byte| time << 8 | u64::from(*byte));
+        let fudge = reader.u16()?;
+        let mac_length = reader.u16()? as usize;
+        let mac = reader.bytes(mac_length)?;
+        reader.u16()?;
+        let error = reader.u16()?;
+        let other_length = reader.u16()? as usize;
+        let other_data = reader.bytes(other_length)?;
+
+        if error != 0 {
+            return Err(Error::DnsUpdateRejected(rcode_name(error)));
+        }
+        if mac.is_empty() && rcode != 0 {
+            return Err(Error::DnsUpdateRejected(rcode_name(rcode)));
+        }
+
+        // the response is signed without its TSIG record and together with the request MAC
+        let mut unsigned = response[..tsig_start].to_vec();
+        unsigned[10..12].copy_from_slice(&(counts[3] - 1).to_be_bytes());
+        let variables = self.tsig_variables(time_signed, fudge, error, other_data)?;
+        let expected = self.mac(&[
+            &(request_mac.len() as u16).to_be_bytes(),
+            request_mac,
+            &unsigned,
+            &variables,
+        ])?;
+        if expected.len() != mac.len() || !memcmp::eq(&expected, mac) {
+            return Err(Error::InvalidTsigSignature);
+        }
+        // a valid signature may still be replayed, so it's only accepted within the fudge
+        // (RFC8945 section 5.2.3)
+        if unix_time().abs_diff(time_signed) > u64::from(fudge) {
+            return Err(Error::InvalidTsigTime);
+        }
+
+        match rcode {
+            0 => Ok(()),
+            _ => Err(Error::DnsUpdateRejected(rcode_name(rcode))),
+        }
+    }
+}
+
+impl DnsPublisher for Rfc2136Publisher {
+    fn publish(&self, record_name: &str, value: &str) -> Result<()> {
+        self.update(record_name, value, CLASS_IN, RECORD_TTL)
+    }
+
+    fn remove(&self, record_name: &str, value: &str) -> Result<()> {
+        self.update(record_name, value, CLASS_NONE, 0)
+    }
+}
+
+/// Reads the fields of a `DNS` message.
+struct Reader<'a> {
+    message: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(message: &'a [u8]) -> Self {
+        Reader {
+            message,
+            position: 0,
+        }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .message
+            .get(self.position..self.position + length)
+            .ok_or(Error::InvalidDnsResponse)?;
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skips a name, which either ends with the root label or a compression pointer.
+    fn skip_name(&mut self) -> Result<()> {
+        loop {
+            let length = self.bytes(1)?[0];
+            match length {
+                0 => return Ok(()),
+                _ if length & 0xc0 == 0xc0 => {
+                    self.bytes(1)?;
+                    return Ok(());
+                }
+                _ => {
+                    self.bytes(length as usize)?;
+                }
+            }
+        }
+    }
+
+    /// Skips a resource record.
+    fn skip_record(&mut self) -> Result<()> {
+        self.skip_name()?;
+        self.bytes(8)?;
+        let length = self.u16()? as usize;
+        self.bytes(length)?;
+
+        Ok(())
+    }
+}
+
+/// Encodes a domain name in the uncompressed wire format, in lower case as it's needed for
+/// the `TSIG` variables.
+fn encode_name(name: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::InvalidDnsName(name.to_owned());
+
+    let mut encoded = Vec::new();
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|label| !label.is_empty())
+    {
+        if label.len() > 63 {
+            return Err(invalid());
+        }
+        encoded.push(label.len() as u8);
+        encoded.extend(label.to_ascii_lowercase().bytes());
+    }
+    encoded.push(0);
+
+    if encoded.len() > 255 {
+        return Err(invalid());
+    }
+
+    Ok(encoded)
+}
+
+/// Encodes the value of a `TXT` record, which consists of strings of at most 255 bytes.
+fn txt_rdata(value: &str) -> Vec<u8> {
+    let mut rdata = Vec::new();
+    for chunk in value.as_bytes().chunks(255) {
+        rdata.push(chunk.len() as u8);
+        rdata.extend_from_slice(chunk);
+    }
+
+    rdata
+}
+
+/// Returns the current time in seconds since the unix epoch, as it's used by `TSIG`.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Returns the name of a response or `TSIG` error code.
+fn rcode_name(rcode: u16) -> String {
+    let name = match rcode {
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        16 => "BADSIG",
+        17 => "BADKEY",
+        18 => "BADTIME",
+        _ => return format!("RCODE{}", rcode),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "c2VjcmV0IGtleSBmb3IgdGhlIGFjbWUgdXBkYXRlcw==";
+
+    fn test_publisher(secret: &str) -> Rfc2136Publisher {
+        Rfc2136Publisher::new(
+            "127.0.0.1:53".parse().unwrap(),
+            "example.org",
+            "acme-key",
+            TsigAlgorithm::HmacSha256,
+            secret,
+        )
+        .unwrap()
+    }
+
+    /// Signs a request like `update` and returns its `MAC`.
+    fn sign_request(publisher: &Rfc2136Publisher, id: u16) -> Vec<u8> {
+        let mut message = Vec::new();
+        for field in [id, OPCODE_UPDATE, 1, 0, 0, 0] {
+            message.extend_from_slice(&field.to_be_bytes());
+        }
+        message.extend(encode_name("example.org").unwrap());
+        message.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        publisher.sign(&mut message, id).unwrap()
+    }
+
+    /// Builds a response like a server does, which is signed together with the request `MAC`.
+    fn signed_response(
+        publisher: &Rfc2136Publisher,
+        id: u16,
+        rcode: u16,
+        request_mac: &[u8],
+        time_signed: u64,
+    ) -> Vec<u8> {
+        let mut message = Vec::new();
+        for field in [id, FLAG_RESPONSE | OPCODE_UPDATE | rcode, 1, 0, 0, 0] {
+            message.extend_from_slice(&field.to_be_bytes());
+        }
+        message.extend(encode_name("example.org").unwrap());
+        message.extend_from_slice(&TYPE_SOA.to_be_bytes());
+        message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        let variables = publisher
+            .tsig_variables(time_signed, TSIG_FUDGE, 0, &[])
+            .unwrap();
+        let mac = publisher
+            .mac(&[
+                &(request_mac.len() as u16).to_be_bytes(),
+                request_mac,
+                &message,
+                &variables,
+            ])
+            .unwrap();
+
+        let mut rdata = encode_name("hmac-sha256").unwrap();
+        rdata.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        rdata.extend_from_slice(&TSIG_FUDGE.to_be_bytes());
+        rdata.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&mac);
+        for field in [id, 0, 0] {
+            rdata.extend_from_slice(&field.to_be_bytes());
+        }
+
+        message.extend(encode_name("acme-key").unwrap());
+        message.extend_from_slice(&TYPE_TSIG.to_be_bytes());
+        message.extend_from_slice(&CLASS_ANY.to_be_bytes());
+        message.extend_from_slice(&0u32.to_be_bytes());
+        message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        message.extend(rdata);
+        message[10..12].copy_from_slice(&1u16.to_be_bytes());
+
+        message
+    }
+
+    #[test]
+    fn encodes_names_in_lower_case() {
+        assert_eq!(
+            encode_name("_acme-challenge.Example.ORG.").unwrap(),
+            b"\x0f_acme-challenge\x07example\x03org\x00"
+        );
+        assert_eq!(encode_name(".").unwrap(), b"\x00");
+    }
+
+    #[test]
+    fn limits_the_label_length() {
+        assert!(encode_name(&format!("{}.org", "a".repeat(63))).is_ok());
+        assert!(matches!(
+            encode_name(&format!("{}.org", "a".repeat(64))),
+            Err(Error::InvalidDnsName(_))
+        ));
+    }
+
+    #[test]
+    fn limits_the_name_length() {
+        let label = "a".repeat(63);
+        // three labels of 63 bytes take 192 bytes, the last label and the root label the rest
+        let longest = format!("{0}.{0}.{0}.{1}", label, "b".repeat(61));
+        assert_eq!(encode_name(&longest).unwrap().len(), 255);
+
+        let too_long = format!("{0}.{0}.{0}.{1}", label, "b".repeat(62));
+        assert!(matches!(
+            encode_name(&too_long),
+            Err(Error::InvalidDnsName(_))
+        ));
+    }
+
+    #[test]
+    fn splits_txt_values_into_strings() {
+        assert_eq!(txt_rdata("abc"), b"\x03abc");
+
+        let value = "a".repeat(255);
+        let rdata = txt_rdata(&value);
+        assert_eq!(rdata.len(), 256);
+        assert_eq!(rdata[0], 255);
+
+        let value = "a".repeat(300);
+        let rdata = txt_rdata(&value);
+        assert_eq!(rdata.len(), 302);
+        assert_eq!(rdata[0], 255);
+        assert_eq!(rdata[256], 45);
+    }
+
+    #[test]
+    fn accepts_signed_responses() {
+        let publisher = test_publisher(SECRET);
+        let request_mac = sign_request(&publisher, 42);
+        let response = signed_response(&publisher, 42, 0, &request_mac, unix_time());
+
+        assert!(publisher
+            .check_response(&response, 42, &request_mac)
+            .is_ok());
+    }
+
+    #[test]
+    fn reports_rejected_updates() {
+        let publisher = test_publisher(SECRET);
+        let request_mac = sign_request(&publisher, 42);
+        let response = signed_response(&publisher, 42, 5, &request_mac, unix_time());
+
+        assert!(matches!(
+            publisher.check_response(&response, 42, &request_mac),
+            Err(Error::DnsUpdateRejected(rcode)) if rcode == "REFUSED"
+        ));
+    }
+
+    #[test]
+    fn rejects_responses_to_other_requests() {
+        let publisher = test_publisher(SECRET);
+        let request_mac = sign_request(&publisher, 42);
+        let response = signed_response(&publisher, 42, 0, &request_mac, unix_time());
+
+        assert!(matches!(
+            publisher.check_response(&response, 43, &request_mac),
+            Err(Error::InvalidDnsResponse)
+        ));
+        // the response is signed together with the MAC of its request
+        let other_mac = sign_request(&publisher, 43);
+        assert!(matches!(
+            publisher.check_response(&response, 42, &other_mac),
+            Err(Error::InvalidTsigSignature)
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_macs() {
+        let publisher = test_publisher(SECRET);
+        let request_mac = sign_request(&publisher, 42);
+
+        let other_publisher = test_publisher("b3RoZXIgc2VjcmV0");
+        let response = signed_response(&other_publisher, 42, 0, &request_mac, unix_time());
+        assert!(matches!(
+            publisher.check_response(&response, 42, &request_mac),
+            Err(Error::InvalidTsigSignature)
+        ));
+
+        // a modified zone name
+        let mut response = signed_response(&publisher, 42, 0, &request_mac, unix_time());
+        response[13] = b'E';
+        assert!(matches!(
+            publisher.check_response(&response, 42, &request_mac),
+            Err(Error::InvalidTsigSignature)
+        ));
+    }
+
+    #[test]
+    fn checks_the_time_signed_against_the_fudge() {
+        let publisher = test_publisher(SECRET);
+        let request_mac = sign_request(&publisher, 42);
+        let fudge = u64::from(TSIG_FUDGE);
+
+        for time_signed in [unix_time() - fudge + 10, unix_time() + fudge - 10] {
+            let response = signed_response(&publisher, 42, 0, &request_mac, time_signed);
+            assert!(publisher
+                .check_response(&response, 42, &request_mac)
+                .is_ok());
+        }
+        for time_signed in [unix_time() - fudge - 10, unix_time() + fudge + 10] {
+            let response = signed_response(&publisher, 42, 0, &request_mac, time_signed);
+            assert!(matches!(
+                publisher.check_response(&response, 42, &request_mac),
+                Err(Error::InvalidTsigTime)
+            ));
+        }
+    }
+}
@@ -1,61 +1,79 @@
+use std::fmt::{self, Display};
 use std::io;
+use std::process::ExitStatus;
 use thiserror::Error;
 
 use openssl::error::ErrorStack;
-use reqwest::header::ToStrError;
+use reqwest::{header::ToStrError, StatusCode};
+use serde::{Deserialize, Serialize};
+
+/// The prefix of all error types defined by the `ACME` specification.
+const ACME_ERROR_PREFIX: &str = "urn:ietf:params:acme:error:";
 
 /// An enumeration of all possible errors.
 #[non_exhaustive]
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("The request specified an account that does not exist")]
-    AccountDoesNotExist,
-    #[error("The request specified a certificate to be revoked that has already been revoked")]
-    AlreadyRevokedCertificate,
-    #[error("The CSR is unacceptable (e.g., due to a short key)")]
-    BadCSR,
-    #[error("The client sent an unacceptable anti-replay nonce")]
-    BadNonce,
-    #[error("The JWS was signed by a public key the server does not support")]
-    BadPublicKey,
-    #[error("The revocation reason provided is not allowed by the server")]
-    BadRevocationReason,
-    #[error("The JWS was signed with an algorithm the server does not support")]
-    BadSignatureAlgorithm,
-    #[error("Certification Authority Authorization (CAA) records forbid the CA from issuing a certificate")]
-    CaaError,
-    #[error("Specific error conditions are indicated in the \"subproblems\" array")]
-    Compound,
-    #[error("The server could not connect to validation target")]
-    Connection,
-    #[error("There was a problem with a DNS query during identifier validation")]
-    DnsError,
-    #[error("The request must include a value for the \"externalAccountBinding\" field")]
-    ExternalAccountRequired,
-    #[error("Response received didn't match the challenge's requirements")]
-    IncorrectResponse,
-    #[error("A contact URL for an account was invalid")]
-    InvalidContact,
-    #[error("The request message was malformed")]
-    MalformedRequest,
-    #[error("The request attempted to finalize an order that is not ready to be finalized")]
-    OrderNotReady,
-    #[error("The request exceeds a rate limit")]
-    RateLimited,
-    #[error("The server will not issue certificates for the identifier")]
-    RejectedIdentifier,
-    #[error("The server experienced an internal error")]
-    InternalServerError,
-    #[error("The server received a TLS error during validation")]
-    TlsError,
-    #[error("The client lacks sufficient authorization")]
-    Unauthorized,
-    #[error("A contact URL for an account used an unsupported protocol scheme")]
-    UnsupportedContact,
-    #[error("An identifier is of an unsupported type")]
-    UnsupportedIdentifier,
-    #[error("Visit the \"instance\" URL and take actions specified there")]
-    UserActionRequired,
+    #[error("The request specified an account that does not exist: {0}")]
+    AccountDoesNotExist(Problem),
+    #[error(
+        "The request specified a certificate to be revoked that has already been revoked: {0}"
+    )]
+    AlreadyRevokedCertificate(Problem),
+    #[error("The CSR is unacceptable (e.g., due to a short key): {0}")]
+    BadCSR(Problem),
+    #[error("The client sent an unacceptable anti-replay nonce: {0}")]
+    BadNonce(Problem),
+    #[error("The JWS was signed by a public key the server does not support: {0}")]
+    BadPublicKey(Problem),
+    #[error("The revocation reason provided is not allowed by the server: {0}")]
+    BadRevocationReason(Problem),
+    #[error("The JWS was signed with an algorithm the server does not support: {0}")]
+    BadSignatureAlgorithm(Problem),
+    #[error("Certification Authority Authorization (CAA) records forbid the CA from issuing a certificate: {0}")]
+    CaaError(Problem),
+    #[error("Specific error conditions are indicated in the \"subproblems\" array: {0}")]
+    Compound(Problem),
+    #[error("The server could not connect to validation target: {0}")]
+    Connection(Problem),
+    #[error("There was a problem with a DNS query during identifier validation: {0}")]
+    DnsError(Problem),
+    #[error("The request must include a value for the \"externalAccountBinding\" field: {0}")]
+    ExternalAccountRequired(Problem),
+    #[error("Response received didn't match the challenge's requirements: {0}")]
+    IncorrectResponse(Problem),
+    #[error("A contact URL for an account was invalid: {0}")]
+    InvalidContact(Problem),
+    #[error("The request message was malformed: {0}")]
+    MalformedRequest(Problem),
+    #[error("The request attempted to finalize an order that is not ready to be finalized: {0}")]
+    OrderNotReady(Problem),
+    #[error("The request exceeds a rate limit: {0}")]
+    RateLimited(Problem),
+    #[error("The server will not issue certificates for the identifier: {0}")]
+    RejectedIdentifier(Problem),
+    #[error("The server experienced an internal error: {0}")]
+    InternalServerError(Problem),
+    #[error("The server received a TLS error during validation: {0}")]
+    TlsError(Problem),
+    #[error("The client lacks sufficient authorization: {0}")]
+    Unauthorized(Problem),
+    #[error("A contact URL for an account used an unsupported protocol scheme: {0}")]
+    UnsupportedContact(Problem),
+    #[error("An identifier is of an unsupported type: {0}")]
+    UnsupportedIdentifier(Problem),
+    #[error("Visit the \"instance\" URL and take actions specified there: {0}")]
+    UserActionRequired(Problem),
+    #[error("The server returned an unknown error: {0}")]
+    UnknownProblem(Problem),
+    #[error("The server responded with the unexpected status {0}")]
+    UnexpectedStatus(StatusCode),
+    #[error("The response of the server is missing the {0} header")]
+    MissingHeader(&'static str),
+    #[error("The response of the server couldn't be processed")]
+    UnexpectedResponse,
+    #[error("No domain was specified for the certificate")]
+    NoDomains,
     #[error("Error reading the string: {0}")]
     FromUtf8Error(#[from] std::str::Utf8Error),
     #[error("Error in reqwest: {0}")]
@@ -68,10 +86,153 @@ pub enum Error {
     FromToStrError(#[from] ToStrError),
     #[error("IO error {0}")]
     FromIoError(#[from] io::Error),
-    #[error("Currently just http challenges are allowed, so this error is raised if no http challenge is present")]
+    #[error("The server did not offer a http challenge for the authorization")]
     NoHttpChallengePresent,
+    #[error("The server did not offer a dns challenge for the authorization")]
+    NoDnsChallengePresent,
+    #[error("The server did not offer a tls-alpn challenge for the authorization")]
+    NoTlsAlpnChallengePresent,
+    #[error("The server did not offer any of the challenges {0} for the authorization")]
+    NoSupportedChallengePresent(String),
+    #[error("Wildcard domains can only be validated with the dns challenge")]
+    WildcardRequiresDnsChallenge,
     #[error("There was no web server found")]
     NoWebServer,
+    #[error("Got {0} web roots for {1} domains, either a single web root or one for every domain is needed")]
+    WebrootCountMismatch(usize, usize),
+    #[error("The authorization for {0} failed")]
+    InvalidAuthorization(String),
+    #[error("The order failed and can't be finalized")]
+    InvalidOrder,
+    #[error("Timed out while waiting for the server to process the request")]
+    Timeout,
+    #[error("The server doesn't know an account for the given key")]
+    NoAccountForKey,
+    #[error("Only RSA keys and ECDSA keys on the P-256 and P-384 curve are supported")]
+    UnsupportedKeyType,
+    #[error("The public key doesn't belong to the private key")]
+    KeyMismatch,
+    #[error("The HMAC key of the external account binding isn't base64url encoded")]
+    InvalidHmacKey,
+    #[error("The terms of service at {0} need to be agreed to before registering an account")]
+    TermsOfServiceNotAgreed(String),
+    #[error("The server requires an external account binding for registering an account")]
+    MissingExternalAccountBinding,
+    #[error(
+        "The certificate has no authority key identifier, which is needed for renewal information"
+    )]
+    MissingAuthorityKeyIdentifier,
+    #[error("The server sent the invalid timestamp {0}")]
+    InvalidTimestamp(String),
+    #[error("The server returned an empty certificate chain")]
+    EmptyCertificateChain,
+    #[error("The TSIG secret isn't base64 encoded")]
+    InvalidTsigSecret,
+    #[error("The name {0} isn't a valid DNS name")]
+    InvalidDnsName(String),
+    #[error("The DNS server sent an invalid response")]
+    InvalidDnsResponse,
+    #[error("The TSIG signature of the DNS response is missing or invalid")]
+    InvalidTsigSignature,
+    #[error(
+        "The TSIG signature of the DNS response was made too far from the local time (BADTIME)"
+    )]
+    InvalidTsigTime,
+    #[error("The DNS server rejected the update with {0}")]
+    DnsUpdateRejected(String),
+    #[error("The hook {0} failed with {1}")]
+    HookFailed(String, ExitStatus),
+}
+
+/// An identifier that a subproblem refers to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identifier {
+    #[serde(rename = "type")]
+    pub identifier_type: String,
+    pub value: String,
+}
+
+/// A problem that only concerns a single identifier of a request (RFC8555 section 6.7.1).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subproblem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub detail: Option<String>,
+    pub identifier: Option<Identifier>,
+}
+
+/// A problem document (RFC7807) that's returned by the server in case of an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    #[serde(rename = "type", default)]
+    pub problem_type: String,
+    pub detail: Option<String>,
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub subproblems: Vec<Subproblem>,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.detail.as_deref().unwrap_or(self.problem_type.as_str())
+        )?;
+
+        for subproblem in &self.subproblems {
+            let identifier = subproblem
+                .identifier
+                .as_ref()
+                .map(|identifier| identifier.value.as_str())
+                .unwrap_or("unknown identifier");
+            let detail = subproblem
+                .detail
+                .as_deref()
+                .unwrap_or(subproblem.problem_type.as_str());
+
+            write!(f, "; {}: {}", identifier, detail)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Problem> for Error {
+    fn from(problem: Problem) -> Self {
+        let error_type = problem
+            .problem_type
+            .strip_prefix(ACME_ERROR_PREFIX)
+            .unwrap_or_default();
+
+        match error_type {
+            "accountDoesNotExist" => Error::AccountDoesNotExist(problem),
+            "alreadyRevoked" => Error::AlreadyRevokedCertificate(problem),
+            "badCSR" => Error::BadCSR(problem),
+            "badNonce" => Error::BadNonce(problem),
+            "badPublicKey" => Error::BadPublicKey(problem),
+            "badRevocationReason" => Error::BadRevocationReason(problem),
+            "badSignatureAlgorithm" => Error::BadSignatureAlgorithm(problem),
+            "caa" => Error::CaaError(problem),
+            "compound" => Error::Compound(problem),
+            "connection" => Error::Connection(problem),
+            "dns" => Error::DnsError(problem),
+            "externalAccountRequired" => Error::ExternalAccountRequired(problem),
+            "incorrectResponse" => Error::IncorrectResponse(problem),
+            "invalidContact" => Error::InvalidContact(problem),
+            "malformed" => Error::MalformedRequest(problem),
+            "orderNotReady" => Error::OrderNotReady(problem),
+            "rateLimited" => Error::RateLimited(problem),
+            "rejectedIdentifier" => Error::RejectedIdentifier(problem),
+            "serverInternal" => Error::InternalServerError(problem),
+            "tls" => Error::TlsError(problem),
+            "unauthorized" => Error::Unauthorized(problem),
+            "unsupportedContact" => Error::UnsupportedContact(problem),
+            "unsupportedIdentifier" => Error::UnsupportedIdentifier(problem),
+            "userActionRequired" => Error::UserActionRequired(problem),
+            _ => Error::UnknownProblem(problem),
+        }
+    }
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -2,57 +2,93 @@
 //! specified in [RFC8555](https://tools.ietf.org/html/rfc8555).
 //!
 //! ## Features
-//! - `acme-rs` in its current state does only support the http challenge. The port 80 must not be blocked as this tool opens a http server in order to complete the challenge <br>
+//! - `acme-rs` supports the http and the dns challenge. For the http challenge the port 80 must not be blocked as this tool opens a http server in order to complete the challenge. The dns challenge hands the needed `TXT` record to a `DnsPublisher`, which is also the only way to get wildcard certificates, and the tls-alpn challenge (RFC8737) is answered by a built-in responder on port 443, so hosts without port 80 can get certificates as well. <br>
 //! - You have the option to generate you keypair for the certificate first before executing the client. <br>
 //! - By default, acme-rs will send the request to the URL https://acme-v02.api.letsencrypt.org/directory. However, you can manually change the ACME Server URL by using the `--server` flag. Just make sure you pass in the URL pointing to the _directory_ information. The client then fetches all paths for further requests from the endpoint.
 //!
 //! # Usage
 //! This crate currently only exposes a few methods. The main method `generate_cert_for_domain` exposes the functionality of the full working process of requesting a SSL/TLS certificate.
 //! It therefore completes the following steps:
-//! - Create a new account for a specialized `email` address.
-//! - Create a new order with that account for a certificate over the specified `domain`.
-//! - Fetch the list of available challenges from the order.
-//! - Complete the http challenge by opening a webserver on port `80`.
+//! - Look up the account of a stored account key or create a new account for a specialized `email` address.
+//! - Create a new order with that account for a certificate over the specified `domains`.
+//! - Fetch the list of available challenges for every domain of the order.
+//! - Complete the http challenge by opening a webserver on port `80` or the dns challenge by publishing a `TXT` record.
+//! - Poll the authorizations and the order until the server validated them.
 //! - Download the certificate from the server and return it.
 //!
-//! The method takes a RSA keypair, the domain, the email and the ACME server url as an input.
+//! The method takes the key of the certificate, which is either a RSA or an ECDSA key (see `KeyType`), and the `Settings` of the request, which hold the domains, the email and the ACME server url among others.
 //!
 //! This method is also used by the binary cli that ships with this crate. Usage instructions for the cli and information about the project in general can be found [here](https://github.com/kariustobias/acme-rs).
 //!
 //! ## Example
 //! ```ignore,rust
-//! use acme_rs::{generate_cert_for_domain, util::{generate_rsa_keypair, save_certificates, save_keypair}};
+//! use std::path::Path;
+//! use acme_rs::{account::AccountStore, generate_cert_for_domain, util::{generate_key, save_certificate_files}, KeyType, Settings};
 //!
-//! // create a keypair and request the certificate for it
-//! let keypair = generate_rsa_keypair().expect("Error during key creation");
-//! let cert_chain = generate_cert_for_domain(
-//!            &keypair,
-//!            None,
-//!            "www.example.org",
+//! // create a key and request the certificate for it
+//! let key = generate_key(KeyType::EcP256).expect("Error during key creation");
+//! let mut settings = Settings::new(
+//!            &["www.example.org", "example.org"],
 //!            "https://acme-v02.api.letsencrypt.org/directory",
 //!            "max@mustermann.de",
-//!            false,
-//!            false,
-//!        ).expect("Error while requesting the certificate.")
+//!        );
+//! settings.account_store = Some(AccountStore::new("accounts"));
+//! settings.terms_of_service_agreed = true;
 //!
-//! // save the certificate in two files called my_cert.crt and cert_chain.crt
-//! save_certificates(cert_chain).expect("Unable to save certificate");
+//! let certificate = generate_cert_for_domain(&key, None, &settings)
+//!        .expect("Error while requesting the certificate.");
+//!
+//! // save the certificate, its chain and the key in the directory certs/www.example.org
+//! save_certificate_files(Path::new("certs"), "www.example.org", &certificate, &key)
+//!        .expect("Unable to save certificate");
 //! ```
 
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use account::{AccountStore, ExternalAccountBinding};
+use certificate::Certificate;
+use dns::DnsPublisher;
 use error::Error;
 use log::info;
+use nonce::NoncePool;
 use openssl::{
-    pkey::{Private, Public},
-    rsa::Rsa,
-    x509::X509Req,
+    pkey::{PKey, Private},
+    x509::{X509Req, X509},
 };
+use renewal::{certificate_identifier, RenewalInfo};
 use reqwest::blocking::Client;
-use types::{Certificate, Directory};
-use util::generate_rsa_key;
+use solver::{
+    ChallengeSolver, DnsSolver, PerDomainSolver, StandaloneSolver, TlsAlpnSolver, WebrootSolver,
+    DNS_01,
+};
+use types::{Account, Directory, Order, StatusType};
+use util::{check_for_existing_server, generate_key};
 
+/// Contains the `AccountStore`, which persists the `ACME` account across runs.
+pub mod account;
+/// Contains the `Certificate` type, which holds the parsed certificate chain.
+pub mod certificate;
+/// Contains the `DnsPublisher` trait which is used to complete the dns challenge
+/// as well as helpers for building the needed `TXT` record.
+pub mod dns;
 /// The module which encapsulates the error enumeration
 /// and related code and types.
 pub mod error;
+/// The pool of anti-replay nonces, which are needed to sign each request.
+mod nonce;
+/// Contains the renewal information (RFC9773), which tells when a certificate should be renewed.
+pub mod renewal;
+/// Contains the `ChallengeSolver` trait, which completes the challenges of the authorizations,
+/// and the built-in solvers.
+pub mod solver;
+/// The standalone `HTTP` server, which serves the tokens of the http challenge.
+mod standalone;
+/// The responder for the tls-alpn challenge (RFC8737), which serves a self-signed
+/// validation certificate on port 443.
+mod tls_alpn;
 /// All types concerning the ACME context. All of the types are
 /// serializable for easy communication.
 mod types;
@@ -60,104 +96,543 @@ mod types;
 /// module heavily uses the `serde_json` and `openssl` libaries.
 pub mod util;
 
-const KEY_WIDTH: u32 = 2048;
+/// The default time that's waited for the server to process a request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The address the standalone server for the http challenge listens on by default. The
+/// challenge is always validated on port 80, so other ports need to be forwarded to.
+pub const DEFAULT_STANDALONE_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 80);
+
+/// The web root the http challenge is written to if no other one is configured.
+pub const DEFAULT_WEB_ROOT: &str = "/var/www/html";
+
+/// The challenge type that's used to prove the control over the domain.
+#[derive(Clone, Copy)]
+pub enum ChallengeType<'a> {
+    /// The http-01 challenge, which is either served by a standalone server or written
+    /// to the web root of an already running server (see `Settings::webroots`).
+    Http { standalone: bool },
+    /// The dns-01 challenge, whose `TXT` record is placed by the given publisher.
+    Dns(&'a dyn DnsPublisher),
+    /// The tls-alpn-01 challenge, which is served by a built-in `TLS` responder on port 443.
+    TlsAlpn,
+    /// Any challenge that's supported by the given solver.
+    Custom(&'a dyn ChallengeSolver),
+}
+
+/// The type of a generated key. Certificate keys of every type are accepted by the common `ACME`
+/// servers, but many `TLS` terminators prefer the smaller `ECDSA` keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyType {
+    /// A RSA key with 2048 bits, which is the default.
+    #[default]
+    Rsa2048,
+    /// A RSA key with 3072 bits.
+    Rsa3072,
+    /// A RSA key with 4096 bits.
+    Rsa4096,
+    /// An ECDSA key on the P-256 curve.
+    EcP256,
+    /// An ECDSA key on the P-384 curve.
+    EcP384,
+}
+
+/// The reason for revoking a certificate, as defined in RFC5280 section 5.3.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevocationReason {
+    Unspecified = 0,
+    KeyCompromise = 1,
+    CaCompromise = 2,
+    AffiliationChanged = 3,
+    Superseded = 4,
+    CessationOfOperation = 5,
+    CertificateHold = 6,
+    RemoveFromCrl = 8,
+    PrivilegeWithdrawn = 9,
+    AaCompromise = 10,
+}
+
+/// The key that's used to sign a revocation request.
+#[derive(Clone, Copy)]
+pub enum RevocationKey<'a> {
+    /// The key of an account that holds the certificate or is authorized for all of its domains.
+    Account(&'a PKey<Private>),
+    /// The private key of the certificate itself.
+    Certificate(&'a PKey<Private>),
+}
+
+/// The settings of a certificate request, which are passed to `generate_cert_for_domain`.
+pub struct Settings<'a> {
+    /// The domains the certificate is issued for. The first domain is used as the common name.
+    pub domains: Vec<String>,
+    /// The URL pointing to the directory information of the `ACME` server.
+    pub server: String,
+    /// The contact email of the account.
+    pub email: String,
+    /// The key of the account. If none is given, the key is loaded from the `account_store`
+    /// or a new one is generated. A key that's given is only saved to the `account_store` if
+    /// it's the stored key, so the stored account is never replaced by it.
+    pub account_key: Option<PKey<Private>>,
+    /// The type of the key that's generated for a new account if no key is given or stored.
+    pub account_key_type: KeyType,
+    /// An optional store that keeps the account key and url across runs. Without a store,
+    /// a new account is registered for every certificate.
+    pub account_store: Option<AccountStore>,
+    /// The external account binding that's needed by some CAs to register a new account.
+    pub external_account_binding: Option<ExternalAccountBinding>,
+    /// The `ARI` identifier of the certificate that's renewed by this request (see
+    /// `renewal::certificate_identifier`), which allows the CA to exempt the renewal from rate limits.
+    pub replaces: Option<String>,
+    /// The common name of the root that's preferred if the server offers alternate certificate
+    /// chains, e.g. `ISRG Root X1`. A chain matches if its topmost certificate is issued by it.
+    pub preferred_chain: Option<String>,
+    /// Whether the terms of service of the server are agreed to. A new account can only be
+    /// registered if they are, or if the server doesn't have any.
+    pub terms_of_service_agreed: bool,
+    /// The challenge that's used to prove the control over the domains.
+    pub challenge_type: ChallengeType<'a>,
+    /// Solvers that complete the challenges of single domains instead of `challenge_type`,
+    /// keyed by the domain. The `*.` prefix of wildcard domains is ignored, so a wildcard domain
+    /// and its base domain are completed with the same solver.
+    pub domain_solvers: HashMap<String, &'a dyn ChallengeSolver>,
+    /// The web roots the token of the http challenge is written to, one for each domain in the
+    /// same order as the domains. A single web root is used for all domains, any other number of
+    /// web roots is an error. Without a web root, the token is written to `DEFAULT_WEB_ROOT` if a
+    /// server is listening on port 80.
+    pub webroots: Vec<PathBuf>,
+    /// The address the standalone server of the http challenge listens on, which is either an
+    /// IPv4 or an IPv6 address. Defaults to `DEFAULT_STANDALONE_ADDRESS`.
+    pub standalone_address: SocketAddr,
+    /// The maximum time that's waited for the server to validate an authorization or
+    /// to issue the certificate.
+    pub timeout: Duration,
+    /// Enables debug output.
+    pub verbose: bool,
+}
+
+impl<'a> Settings<'a> {
+    /// Creates the settings for requesting a certificate over the domains at a certain server. By
+    /// default the http challenge is completed via an already running web server and no account
+    /// store is used.
+    pub fn new<T: AsRef<str>>(domains: &[T], server: &str, email: &str) -> Self {
+        Settings {
+            domains: domains
+                .iter()
+                .map(|domain| domain.as_ref().to_owned())
+                .collect(),
+            server: server.to_owned(),
+            email: email.to_owned(),
+            account_key: None,
+            account_key_type: KeyType::default(),
+            account_store: None,
+            external_account_binding: None,
+            replaces: None,
+            preferred_chain: None,
+            terms_of_service_agreed: false,
+            challenge_type: ChallengeType::Http { standalone: false },
+            domain_solvers: HashMap::new(),
+            webroots: Vec::new(),
+            standalone_address: DEFAULT_STANDALONE_ADDRESS,
+            timeout: DEFAULT_TIMEOUT,
+            verbose: false,
+        }
+    }
+
+    /// Returns the solver that completes the challenges of the configured challenge type and
+    /// the domain solvers.
+    fn solver(&self) -> Result<Box<dyn ChallengeSolver + 'a>, Error> {
+        if self.domain_solvers.is_empty() {
+            return self.challenge_type_solver();
+        }
+
+        let mut solver = PerDomainSolver::new(self.challenge_type_solver()?);
+        for (domain, domain_solver) in &self.domain_solvers {
+            solver.insert(domain, *domain_solver);
+        }
+
+        Ok(Box::new(solver))
+    }
 
-/// Generates a certificate for a certain domain. This method contains the logic for communicating with
-/// the server in order to authenticate for the certificate. The keypair that's passed to this method is
-/// used to sign the certificate signing request (CSR). In case a pre loaded CSR is passed in, the keypair
+    /// Returns the solver that completes the challenges of the configured challenge type.
+    fn challenge_type_solver(&self) -> Result<Box<dyn ChallengeSolver + 'a>, Error> {
+        Ok(match self.challenge_type {
+            ChallengeType::Http { standalone: true } => {
+                Box::new(StandaloneSolver::new(self.standalone_address))
+            }
+            ChallengeType::Http { standalone: false } => match self.webroots.as_slice() {
+                [webroot] => Box::new(WebrootSolver::new(webroot)),
+                [] if check_for_existing_server() => Box::new(WebrootSolver::new(DEFAULT_WEB_ROOT)),
+                [] => Box::new(WebrootSolver::default()),
+                webroots if webroots.len() == self.domains.len() => {
+                    Box::new(WebrootSolver::for_domains(
+                        self.domains
+                            .iter()
+                            .cloned()
+                            .zip(webroots.iter().cloned())
+                            .collect(),
+                    ))
+                }
+                webroots => {
+                    return Err(Error::WebrootCountMismatch(
+                        webroots.len(),
+                        self.domains.len(),
+                    ))
+                }
+            },
+            ChallengeType::Dns(publisher) => Box::new(DnsSolver::new(publisher)),
+            ChallengeType::TlsAlpn => Box::new(TlsAlpnSolver::new()),
+            ChallengeType::Custom(solver) => Box::new(solver),
+        })
+    }
+}
+
+/// Generates a certificate for a list of domains. The first domain is used as the common name
+/// of the certificate, while all of them are added as subject alternative names. Wildcard domains
+/// like `*.example.org` need to be validated with `ChallengeType::Dns`. This method contains the logic for communicating with
+/// the server in order to authenticate for the certificate. The key that's passed to this method is
+/// used to sign the certificate signing request (CSR). In case a pre loaded CSR is passed in, the key
 /// needs to be the same as the one that signed the CSR.
 /// # Example
 /// ```ignore,rust
-/// use acme_rs::{generate_cert_for_domain, util::{generate_rsa_keypair, save_certificates, save_keypair}};
+/// use std::path::Path;
+/// use acme_rs::{account::AccountStore, generate_cert_for_domain, util::{generate_key, save_certificate_files}, KeyType, Settings};
 ///
-/// // create a keypair and request the certificate for it
-/// let keypair = generate_rsa_keypair().expect("Error during key creation");
-/// let cert_chain = generate_cert_for_domain(
-///            &keypair,
-///            None,
-///            "www.example.org",
+/// // create a key and request the certificate for it
+/// let key = generate_key(KeyType::EcP256).expect("Error during key creation");
+/// let mut settings = Settings::new(
+///            &["www.example.org", "example.org"],
 ///            "https://acme-v02.api.letsencrypt.org/directory",
 ///            "max@mustermann.de",
-///            false,
-///            false,
-///        ).expect("Error while requesting the certificate.")
+///        );
+/// settings.account_store = Some(AccountStore::new("accounts"));
+/// settings.terms_of_service_agreed = true;
+///
+/// let certificate = generate_cert_for_domain(&key, None, &settings)
+///        .expect("Error while requesting the certificate.");
 ///
-/// // save the certificate in two files called my_cert.crt and cert_chain.crt
-/// save_certificates(cert_chain).expect("Unable to save certificate");
+/// // save the certificate, its chain and the key in the directory certs/www.example.org
+/// save_certificate_files(Path::new("certs"), "www.example.org", &certificate, &key)
+///        .expect("Unable to save certificate");
 /// ```
-pub fn generate_cert_for_domain<T: AsRef<str>>(
-    keypair_for_cert: &(Rsa<Private>, Rsa<Public>),
+pub fn generate_cert_for_domain(
+    key_for_cert: &PKey<Private>,
     optional_csr: Option<X509Req>,
-    domain: T,
-    server: T,
-    email: T,
-    standalone: bool,
-    verbose: bool,
+    settings: &Settings,
 ) -> Result<Certificate, Error> {
-    // this keypair is used for authentificating the requests, but does not matter afterwards
-    let keypair = generate_rsa_key()?;
+    let verbose = settings.verbose;
+    let domains = &settings.domains;
+
+    // the keypair that's used for authentificating the requests is either passed in,
+    // loaded from the account store or generated for this request
+    let (stored_key, stored_url) = match &settings.account_store {
+        Some(store) => (store.load_key()?, store.load_account_url()?),
+        None => (None, None),
+    };
+    // a passed in key only belongs to the stored account if it's the stored key
+    let uses_stored_key = match (&settings.account_key, &stored_key) {
+        (Some(key), Some(stored_key)) => key.public_eq(stored_key),
+        (None, stored_key) => stored_key.is_some(),
+        (Some(_), None) => false,
+    };
+    // the stored url only belongs to the stored key
+    let stored_url = stored_url.filter(|_| uses_stored_key);
+    // other passed in keys are never stored, so they don't replace the stored account
+    let persist_account = settings.account_key.is_none() || uses_stored_key;
+    let (keypair, has_existing_key) = match (&settings.account_key, stored_key) {
+        (Some(key), _) => (key.clone(), true),
+        (None, Some(key)) => (key, true),
+        (None, None) => (generate_key(settings.account_key_type)?, false),
+    };
     // create a new client (passed through to each step to make use of the keep-alive function)
     let client = Client::new();
 
-    // fetch the directory infos an create a new account
-    let dir_infos = Directory::fetch_dir(&client, server.as_ref())?;
-    let new_acc = dir_infos.create_account(&client, &keypair, email.as_ref())?;
+    // fetch the directory infos and look up the existing account or create a new one
+    let dir_infos = Directory::fetch_dir(&client, &settings.server)?;
+    let nonces = NoncePool::new(&dir_infos.new_nonce);
     if verbose {
-        info!("Created account: {:#?}", new_acc);
+        info!(
+            "Fetched the directory with the meta data: {:#?}",
+            dir_infos.meta
+        );
+    }
+    // the url of a stored account is known already, so it doesn't need to be looked up
+    let existing_acc = match &stored_url {
+        Some(url) => Some(Account::from_location(url)),
+        None if has_existing_key => dir_infos.find_account(&client, &nonces, &keypair)?,
+        None => None,
+    };
+    let new_acc = match existing_acc {
+        Some(account) => {
+            if verbose {
+                info!("Found existing account: {:#?}", account);
+            }
+            account
+        }
+        None => {
+            let account = dir_infos.create_account(
+                &client,
+                &nonces,
+                &keypair,
+                &settings.email,
+                settings.external_account_binding.as_ref(),
+                settings.terms_of_service_agreed,
+            )?;
+            if verbose {
+                info!("Created account: {:#?}", account);
+            }
+            account
+        }
+    };
+
+    // persist the account, so it's reused on the next run
+    if let (Some(store), true, None) = (&settings.account_store, persist_account, &stored_url) {
+        store.save(&keypair, &new_acc.account_location)?;
+        if verbose {
+            info!("Saved the account to {}", store.path().display());
+        }
     }
 
-    // create a new order
+    // create a new order, the replaced certificate is only sent to servers that support
+    // renewal information
+    let replaces = settings
+        .replaces
+        .as_deref()
+        .filter(|_| dir_infos.renewal_info.is_some());
     let order = new_acc.create_new_order(
         &client,
+        &nonces,
         &dir_infos.new_order,
         &keypair,
-        domain.as_ref(),
-        optional_csr,
+        domains,
+        replaces,
     )?;
     if verbose {
         info!(
-            "Opened new order for domain {}: {:#?}",
-            domain.as_ref(),
+            "Opened new order for domains {}: {:#?}",
+            domains.join(", "),
             &order
         );
     }
 
-    // fetch the auth challenges
-    let challenge = order.fetch_auth_challenges(&client, &new_acc.account_location, &keypair)?;
-    if verbose {
-        info!(
-            "Got the following authorization challenges: {:#?}",
-            &challenge
-        );
+    // the solver is shared by all authorizations, e.g. the standalone server serves the tokens
+    // of all of them
+    let solver = settings.solver()?;
+
+    // every domain has its own authorization, so each of them needs to be completed
+    for auth_url in &order.authorizations {
+        // fetch the auth challenges
+        let challenge = order.fetch_auth_challenges(
+            &client,
+            auth_url,
+            &new_acc.account_location,
+            &nonces,
+            &keypair,
+        )?;
+        if verbose {
+            info!(
+                "Got the following authorization challenges: {:#?}",
+                &challenge
+            );
+        }
+
+        // authorizations of the account may be reused by the server, these are already valid
+        if challenge.status == StatusType::Valid {
+            if verbose {
+                info!("The authorization {} is already valid", auth_url);
+            }
+            continue;
+        }
+
+        // the server only offers the dns challenge for wildcard domains
+        if challenge.is_wildcard()
+            && !solver
+                .challenge_types(challenge.domain()?)
+                .contains(&DNS_01)
+        {
+            return Err(Error::WildcardRequiresDnsChallenge);
+        }
+
+        // complete the challenge
+        challenge.complete_challenge(
+            &client,
+            &nonces,
+            &new_acc.account_location,
+            &keypair,
+            solver.as_ref(),
+            settings.timeout,
+        )?;
+        if verbose {
+            info!("Succesfully completed the challenge for {}", auth_url);
+        }
     }
 
-    // complete the challenge and save the nonce that's needed for further authentification
-    let new_nonce = challenge.complete_http_challenge(
+    // all authorizations are final, so the solver can release its resources, e.g. the
+    // standalone server frees its port
+    drop(solver);
+
+    // wait until the server processed all authorizations
+    order.wait_until_ready(
         &client,
         &new_acc.account_location,
+        &nonces,
         &keypair,
-        standalone,
+        settings.timeout,
     )?;
-    if verbose {
-        info!("Succesfully completed the http challenge");
-    }
 
-    // finalize the order to retrieve location of the final cert
-    let updated_order = order.finalize_order(
+    // finalize the order and wait until the certificate is issued
+    let csr = match optional_csr {
+        Some(csr) => csr,
+        None => Order::request_csr(key_for_cert, domains)?,
+    };
+    let updated_order = order
+        .finalize_order(&client, &new_acc.account_location, &nonces, &keypair, &csr)?
+        .wait_until_valid(
+            &client,
+            &nonces,
+            &new_acc.account_location,
+            &keypair,
+            settings.timeout,
+        )?;
+
+    // download the certificate
+    let cert_chain = updated_order.download_certificate(
         &client,
+        &nonces,
         &new_acc.account_location,
-        new_nonce,
         &keypair,
-        keypair_for_cert,
-        domain.as_ref(),
+        settings.preferred_chain.as_deref(),
     )?;
-
-    // download the certificate
-    let cert_chain =
-        updated_order.download_certificate(&client, &new_acc.account_location, &keypair)?;
     if verbose {
-        info!("Received the following certificate chain: {}", cert_chain);
+        info!(
+            "Received the certificate for {} valid until {}",
+            cert_chain.subject_alt_names().join(", "),
+            cert_chain.leaf().not_after()
+        );
     }
 
     Ok(cert_chain)
 }
+
+/// Revokes a certificate at the given server with the reason code. The request is either signed
+/// by an account key or by the private key of the certificate (RFC8555 section 7.6), which allows
+/// revoking a certificate even if the account that requested it was lost.
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{revoke_certificate, util::{load_certificate_from_file, load_private_key_from_file}, RevocationKey, RevocationReason};
+///
+/// let certificate = load_certificate_from_file("my_cert.crt").expect("Error loading the certificate");
+/// let key = load_private_key_from_file("priv.pem").expect("Error loading the key");
+///
+/// revoke_certificate(
+///        "https://acme-v02.api.letsencrypt.org/directory",
+///        &certificate,
+///        RevocationReason::KeyCompromise,
+///        RevocationKey::Certificate(&key),
+///    )
+///    .expect("Error while revoking the certificate");
+/// ```
+pub fn revoke_certificate(
+    server: &str,
+    certificate: &X509,
+    reason: RevocationReason,
+    key: RevocationKey,
+) -> Result<(), Error> {
+    let client = Client::new();
+    let dir_infos = Directory::fetch_dir(&client, server)?;
+    let nonces = NoncePool::new(&dir_infos.new_nonce);
+
+    match key {
+        RevocationKey::Account(p_key) => {
+            let account = dir_infos
+                .find_account(&client, &nonces, p_key)?
+                .ok_or(Error::NoAccountForKey)?;
+
+            dir_infos.revoke_certificate(
+                &client,
+                &nonces,
+                certificate,
+                reason as u8,
+                p_key,
+                Some(&account.account_location),
+            )
+        }
+        RevocationKey::Certificate(p_key) => {
+            dir_infos.revoke_certificate(&client, &nonces, certificate, reason as u8, p_key, None)
+        }
+    }
+}
+
+/// Rolls the account that belongs to `old_key` over to `new_key` (RFC8555 section 7.3.5), e.g. if
+/// the old key was compromised. The account keeps its url and order history. If an account store
+/// is passed in that holds this account, the new key is saved to it once the server confirmed the
+/// change. A store that holds a different account is left as it is.
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{account::AccountStore, change_account_key, util::generate_ec_key};
+/// use openssl::nid::Nid;
+///
+/// let store = AccountStore::new("accounts");
+/// let old_key = store.load_key().expect("Error loading the key").expect("No account key");
+/// let new_key = generate_ec_key(Nid::X9_62_PRIME256V1).expect("Error during key creation");
+///
+/// change_account_key(
+///        "https://acme-v02.api.letsencrypt.org/directory",
+///        &old_key,
+///        &new_key,
+///        Some(&store),
+///    )
+///    .expect("Error while changing the account key");
+/// ```
+pub fn change_account_key(
+    server: &str,
+    old_key: &PKey<Private>,
+    new_key: &PKey<Private>,
+    account_store: Option<&AccountStore>,
+) -> Result<(), Error> {
+    let client = Client::new();
+    let dir_infos = Directory::fetch_dir(&client, server)?;
+    let nonces = NoncePool::new(&dir_infos.new_nonce);
+
+    let account = dir_infos
+        .find_account(&client, &nonces, old_key)?
+        .ok_or(Error::NoAccountForKey)?;
+
+    account.change_key(&client, &nonces, &dir_infos.key_change, old_key, new_key)?;
+
+    if let Some(store) = account_store {
+        // the account is stored either with its url or, if the url is missing, with the old key
+        let stores_account = match store.load_account_url()? {
+            Some(url) => url == account.account_location,
+            None => store
+                .load_key()?
+                .is_some_and(|stored_key| stored_key.public_eq(old_key)),
+        };
+        if stores_account {
+            store.save(new_key, &account.account_location)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the renewal information (RFC9773) of a certificate, which holds the window in which
+/// the CA wants the certificate to be renewed. Returns `None` if the server doesn't support it.
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{fetch_renewal_info, util::load_certificate_from_file};
+///
+/// let certificate = load_certificate_from_file("my_cert.crt").expect("Error loading the certificate");
+///
+/// let renewal_info = fetch_renewal_info("https://acme-v02.api.letsencrypt.org/directory", &certificate)
+///     .expect("Error while fetching the renewal information");
+/// if renewal_info.map_or(false, |info| info.should_renew()) {
+///     // request a new certificate
+/// }
+/// ```
+pub fn fetch_renewal_info(server: &str, certificate: &X509) -> Result<Option<RenewalInfo>, Error> {
+    let client = Client::new();
+    let dir_infos = Directory::fetch_dir(&client, server)?;
+
+    dir_infos.fetch_renewal_info(&client, &certificate_identifier(certificate)?)
+}
@@ -0,0 +1,87 @@
+use std::sync::{Mutex, PoisonError};
+
+use openssl::pkey::{PKey, Private};
+use reqwest::blocking::{Client, Response};
+use serde_json::json;
+
+use crate::{
+    error::{Error, Result},
+    types::Nonce,
+    util::{check_response, extract_nonce, jws},
+};
+
+/// Collects the anti-replay nonces (RFC8555 section 6.5) that the server sends with
+/// every response, so each request can be signed with an unused one.
+#[derive(Debug)]
+pub struct NoncePool {
+    new_nonce_url: String,
+    nonces: Mutex<Vec<Nonce>>,
+}
+
+impl NoncePool {
+    /// Creates an empty pool that fetches new nonces from the given `newNonce` endpoint.
+    pub fn new(new_nonce_url: &str) -> Self {
+        NoncePool {
+            new_nonce_url: new_nonce_url.to_owned(),
+            nonces: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns an unused nonce. If the pool ran out of nonces, a fresh one is
+    /// fetched from the `newNonce` endpoint.
+    pub fn take(&self, client: &Client) -> Result<Nonce> {
+        let nonce = self
+            .nonces
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+
+        match nonce {
+            Some(nonce) => Ok(nonce),
+            None => extract_nonce(&check_response(client.head(&self.new_nonce_url).send()?)?),
+        }
+    }
+
+    /// Stores the `replay-nonce` header field of a response. The server sends one
+    /// with every response, including errors.
+    pub fn store(&self, response: &Response) {
+        if let Ok(nonce) = extract_nonce(response) {
+            self.nonces
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(nonce);
+        }
+    }
+
+    /// Signs the payload with an unused nonce and sends it to the url. If the server rejects
+    /// the nonce (`badNonce`), the request is retried once with a fresh nonce as recommended in
+    /// RFC8555 section 6.5. Returns the response after it was checked for errors.
+    pub fn post(
+        &self,
+        client: &Client,
+        url: &str,
+        header: serde_json::Value,
+        payload: serde_json::Value,
+        private_key: &PKey<Private>,
+    ) -> Result<Response> {
+        let mut retried = false;
+        loop {
+            let mut header = header.clone();
+            header["nonce"] = json!(self.take(client)?);
+
+            let jws = jws(payload.clone(), header, private_key)?;
+
+            let response = client
+                .post(url)
+                .header("Content-Type", "application/jose+json")
+                .body(serde_json::to_string_pretty(&jws)?)
+                .send()?;
+            self.store(&response);
+
+            match check_response(response) {
+                Err(Error::BadNonce(_)) if !retried => retried = true,
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use openssl::{rand::rand_bytes, x509::X509Ref};
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    util::b64,
+};
+
+/// The renewal information of a certificate (RFC9773), which tells when the CA wants the
+/// certificate to be renewed.
+#[derive(Debug, Clone)]
+pub struct RenewalInfo {
+    /// The start of the window in which the certificate should be renewed.
+    pub window_start: SystemTime,
+    /// The end of the window in which the certificate should be renewed.
+    pub window_end: SystemTime,
+    /// An optional url that explains why the window was chosen, e.g. an incident report.
+    pub explanation_url: Option<String>,
+    /// The time after which the renewal information should be fetched again.
+    pub retry_after: Option<Duration>,
+}
+
+impl RenewalInfo {
+    /// Returns whether the certificate should be renewed now, which is the case once a random
+    /// time within the suggested window passed (see `renewal_time`). A window that lies in the
+    /// past means the certificate should be renewed immediately, e.g. because it's going to be
+    /// revoked.
+    pub fn should_renew(&self) -> bool {
+        SystemTime::now() >= self.renewal_time()
+    }
+
+    /// Picks a uniformly random time in the window from `window_start` (inclusive) to
+    /// `window_end` (exclusive), so the renewals of many clients are spread over the window
+    /// (RFC9773 section 4.2). A new time is picked on every call.
+    pub fn renewal_time(&self) -> SystemTime {
+        let window = self
+            .window_end
+            .duration_since(self.window_start)
+            .unwrap_or_default();
+        let window_nanos = u64::try_from(window.as_nanos()).unwrap_or(u64::MAX);
+
+        let mut random = [0; 8];
+        if window_nanos == 0 || rand_bytes(&mut random).is_err() {
+            return self.window_start;
+        }
+
+        // scales the random number down to the window, which is always less than its length
+        let offset = (u128::from(u64::from_be_bytes(random)) * u128::from(window_nanos)) >> 64;
+        self.window_start + Duration::from_nanos(offset as u64)
+    }
+}
+
+/// The renewal information as it's returned by the server.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RenewalInfoResponse {
+    suggested_window: SuggestedWindow,
+    #[serde(rename = "explanationURL")]
+    explanation_url: Option<String>,
+}
+
+/// The window in which the certificate should be renewed, as RFC3339 timestamps.
+#[derive(Debug, Deserialize)]
+struct SuggestedWindow {
+    start: String,
+    end: String,
+}
+
+impl RenewalInfoResponse {
+    /// Parses the timestamps of the suggested window.
+    pub(crate) fn into_renewal_info(self, retry_after: Option<Duration>) -> Result<RenewalInfo> {
+        Ok(RenewalInfo {
+            window_start: parse_rfc3339(&self.suggested_window.start)?,
+            window_end: parse_rfc3339(&self.suggested_window.end)?,
+            explanation_url: self.explanation_url,
+            retry_after,
+        })
+    }
+}
+
+/// Computes the unique identifier of a certificate that's used for fetching its renewal
+/// information and for the `replaces` field of a new order (RFC9773 section 4.1). It consists
+/// of the key identifier of the authority key identifier extension and the serial number.
+pub fn certificate_identifier(certificate: &X509Ref) -> Result<String> {
+    let key_identifier = certificate
+        .authority_key_id()
+        .ok_or(Error::MissingAuthorityKeyIdentifier)?;
+
+    // the serial is encoded like the value of a DER integer, which needs a leading zero byte
+    // if the highest bit is set
+    let mut serial = certificate.serial_number().to_bn()?.to_vec();
+    if serial.first().is_none_or(|byte| byte & 0x80 != 0) {
+        serial.insert(0, 0);
+    }
+
+    Ok(format!(
+        "{}.{}",
+        b64(key_identifier.as_slice()),
+        b64(&serial)
+    ))
+}
+
+/// Parses a RFC3339 timestamp like `2025-01-02T04:00:00.123Z` or `2025-01-02T06:00:00+02:00`.
+fn parse_rfc3339(timestamp: &str) -> Result<SystemTime> {
+    let invalid = || Error::InvalidTimestamp(timestamp.to_owned());
+    let bytes = timestamp.as_bytes();
+    let number = |range: Range<usize>, max: i64| -> Result<i64> {
+        bytes
+            .get(range)
+            .filter(|digits| digits.iter().all(u8::is_ascii_digit))
+            .map(|digits| {
+                digits
+                    .iter()
+                    .fold(0, |number, digit| number * 10 + i64::from(digit - b'0'))
+            })
+            .filter(|number| *number <= max)
+            .ok_or_else(invalid)
+    };
+    let separator = |position: usize, separators: &[u8]| -> Result<()> {
+        match bytes.get(position) {
+            Some(byte) if separators.contains(byte) => Ok(()),
+            _ => Err(invalid()),
+        }
+    };
+
+    separator(4, b"-")?;
+    separator(7, b"-")?;
+    separator(10, b"Tt")?;
+    separator(13, b":")?;
+    separator(16, b":")?;
+    let (year, month) = (number(0..4, 9999)?, number(5..7, 12)?);
+    let day = number(8..10, days_in_month(year, month))?;
+    if month == 0 || day == 0 {
+        return Err(invalid());
+    }
+    // the second may be 60 during a leap second
+    let (hour, minute, second) = (
+        number(11..13, 23)?,
+        number(14..16, 59)?,
+        number(17..19, 60)?,
+    );
+
+    // skip the fractional seconds, which have at least one digit, and parse the offset to UTC
+    let mut offset_start = 19;
+    if bytes.get(offset_start) == Some(&b'.') {
+        let digits = bytes[offset_start + 1..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return Err(invalid());
+        }
+        offset_start += digits + 1;
+    }
+    let offset_seconds = match &bytes[offset_start..] {
+        b"Z" | b"z" => 0,
+        [sign, _, _, b':', _, _] => {
+            let sign = match sign {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(invalid()),
+            };
+            let hours = number(offset_start + 1..offset_start + 3, 23)?;
+            let minutes = number(offset_start + 4..offset_start + 6, 59)?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - offset_seconds;
+
+    Ok(if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    })
+}
+
+/// Returns the number of days of the month in a year of the gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days since the unix epoch for a date of the gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::x509::X509;
+
+    /// A certificate with the authority key identifier and serial of the example in RFC9773
+    /// section 4.1.
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBYjCCAQigAwIBAgIFAIdlQyEwCgYIKoZIzj0EAwIwFTETMBEGA1UEAwwKRXhh
+bXBsZSBDQTAgFw0yNjEwMTcyMjE1NTJaGA8yMTI2MDkyMzIyMTU1MlowFjEUMBIG
+A1UEAwwLZXhhbXBsZS5vcmcwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAS5Hzy7
+4fTM7sN5B4mP0QVyrkJj1GZTaUm4HPThIS5JcHAhlsHbHavjmTXHOJ3w6Ij8W4E6
+1s3o7lF0jOa/JWHXo0IwQDAfBgNVHSMEGDAWgBRpiFtrh0ZAQeGze4R7oK4s3gHI
+1DAdBgNVHQ4EFgQUrgSjzbene3tls2SVoko/lYSewsgwCgYIKoZIzj0EAwIDSAAw
+RQIhAJpfkZ6lglc8pn6lwf2yePRK68Yuphzt1m2tURbu970oAiAN6ibPtyqb5CbW
+TJyf4B+3AeZ8U+Te3IY/xi50fuZDmg==
+-----END CERTIFICATE-----
+";
+
+    fn unix_time(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn certificate_identifier_matches_rfc_example() {
+        let certificate = X509::from_pem(CERTIFICATE.as_bytes()).unwrap();
+
+        assert_eq!(
+            certificate_identifier(&certificate).unwrap(),
+            "aYhba4dGQEHhs3uEe6CuLN4ByNQ.AIdlQyE"
+        );
+    }
+
+    #[test]
+    fn parses_utc_timestamps() {
+        assert_eq!(
+            parse_rfc3339("2025-01-02T04:00:00Z").unwrap(),
+            unix_time(1_735_790_400)
+        );
+        assert_eq!(parse_rfc3339("1970-01-01t00:00:00z").unwrap(), UNIX_EPOCH);
+        assert_eq!(
+            parse_rfc3339("2024-02-29T23:59:59Z").unwrap(),
+            unix_time(1_709_251_199)
+        );
+    }
+
+    #[test]
+    fn skips_fractional_seconds() {
+        assert_eq!(
+            parse_rfc3339("2025-01-02T04:00:00.123456Z").unwrap(),
+            unix_time(1_735_790_400)
+        );
+        assert!(parse_rfc3339("2025-01-02T04:00:00.Z").is_err());
+    }
+
+    #[test]
+    fn applies_offsets() {
+        assert_eq!(
+            parse_rfc3339("2025-01-02T06:00:00+02:00").unwrap(),
+            unix_time(1_735_790_400)
+        );
+        assert_eq!(
+            parse_rfc3339("2025-01-01T22:30:00.5-05:30").unwrap(),
+            unix_time(1_735_790_400)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        for timestamp in [
+            "2025-01-02 04:00:00Z",
+            "2025/01/02T04:00:00Z",
+            "2025-01-02T04-00-00Z",
+            "2025-13-02T04:00:00Z",
+            "2025-00-02T04:00:00Z",
+            "2025-02-29T04:00:00Z",
+            "2025-01-32T04:00:00Z",
+            "2025-01-02T24:00:00Z",
+            "2025-01-02T04:60:00Z",
+            "2025-01-02T04:00:61Z",
+            "2025-01-02T04:00:00",
+            "2025-01-02T04:00:00+0200",
+            "2025-01-02T04:00:00+24:00",
+            "2025-01-02T04:00:00*02:00",
+            "2025-+1-02T04:00:00Z",
+        ] {
+            assert!(parse_rfc3339(timestamp).is_err(), "{}", timestamp);
+        }
+    }
+
+    #[test]
+    fn renewal_time_lies_in_window() {
+        let info = RenewalInfo {
+            window_start: unix_time(1_000),
+            window_end: unix_time(1_010),
+            explanation_url: None,
+            retry_after: None,
+        };
+
+        for _ in 0..1000 {
+            let renewal_time = info.renewal_time();
+            assert!(renewal_time >= info.window_start && renewal_time < info.window_end);
+        }
+    }
+
+    #[test]
+    fn renewal_time_of_empty_window_is_its_start() {
+        let info = RenewalInfo {
+            window_start: unix_time(1_000),
+            window_end: unix_time(900),
+            explanation_url: None,
+            retry_after: None,
+        };
+
+        assert_eq!(info.renewal_time(), info.window_start);
+    }
+}
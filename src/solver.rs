@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, PoisonError};
+
+use log::warn;
+
+pub use crate::types::{Challenge, StatusType};
+use crate::{
+    dns::{self, DnsPublisher},
+    error::{Error, Result},
+    standalone::StandaloneServer,
+    tls_alpn::TlsAlpnResponder,
+};
+
+/// The type of the http challenge (RFC8555 section 8.3).
+pub const HTTP_01: &str = "http-01";
+/// The type of the dns challenge (RFC8555 section 8.4).
+pub const DNS_01: &str = "dns-01";
+/// The type of the tls-alpn challenge (RFC8737).
+pub const TLS_ALPN_01: &str = "tls-alpn-01";
+
+/// The directory below the web root the tokens of the http challenge are served from.
+const CHALLENGE_PATH: &str = ".well-known/acme-challenge";
+
+/// A solver proves the control over an identifier by completing one of the challenges the server
+/// offers for its authorization. Implement this trait in order to complete the challenges with
+/// your own infrastructure, e.g. a load balancer or a custom `DNS` setup.
+///
+/// # Example
+/// ```ignore,rust
+/// use acme_rs::{error::Result, solver::{Challenge, ChallengeSolver, HTTP_01}, ChallengeType};
+///
+/// struct LoadBalancerSolver;
+///
+/// impl ChallengeSolver for LoadBalancerSolver {
+///     fn challenge_types(&self, _identifier: &str) -> &[&str] {
+///         &[HTTP_01]
+///     }
+///
+///     fn present(&self, identifier: &str, challenge: &Challenge, key_authorization: &str) -> Result<()> {
+///         // serve the key authorization under /.well-known/acme-challenge/<token>
+///         Ok(())
+///     }
+///
+///     fn cleanup(&self, identifier: &str, challenge: &Challenge, key_authorization: &str) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// settings.challenge_type = ChallengeType::Custom(&LoadBalancerSolver);
+/// ```
+pub trait ChallengeSolver {
+    /// The types of the challenges (e.g. `http-01`) this solver is able to complete for the
+    /// identifier, in the order of preference.
+    fn challenge_types(&self, identifier: &str) -> &[&str];
+
+    /// Makes the key authorization of the challenge available to the server. The identifier is
+    /// the domain of the authorization, without the `*.` prefix of wildcard domains.
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()>;
+
+    /// Removes what was presented once the authorization is final, regardless of whether it
+    /// was validated or not. It's also called if `present` failed, so it needs to cope with
+    /// a key authorization that was only partly presented.
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()>;
+}
+
+impl<T: ChallengeSolver + ?Sized> ChallengeSolver for &T {
+    fn challenge_types(&self, identifier: &str) -> &[&str] {
+        (**self).challenge_types(identifier)
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        (**self).present(identifier, challenge, key_authorization)
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        (**self).cleanup(identifier, challenge, key_authorization)
+    }
+}
+
+impl<T: ChallengeSolver + ?Sized> ChallengeSolver for Box<T> {
+    fn challenge_types(&self, identifier: &str) -> &[&str] {
+        (**self).challenge_types(identifier)
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        (**self).present(identifier, challenge, key_authorization)
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        (**self).cleanup(identifier, challenge, key_authorization)
+    }
+}
+
+/// Completes the http challenge with a built-in `HTTP` server. The server is started for the
+/// first challenge, serves the tokens of all authorizations and is stopped once the solver
+/// is dropped.
+pub struct StandaloneSolver {
+    address: SocketAddr,
+    server: Mutex<Option<StandaloneServer>>,
+}
+
+impl StandaloneSolver {
+    /// Creates a solver whose server listens on the address, which needs to be reachable on
+    /// port 80 from the outside.
+    pub fn new(address: SocketAddr) -> Self {
+        StandaloneSolver {
+            address,
+            server: Mutex::new(None),
+        }
+    }
+}
+
+impl ChallengeSolver for StandaloneSolver {
+    fn challenge_types(&self, _identifier: &str) -> &[&str] {
+        &[HTTP_01]
+    }
+
+    fn present(
+        &self,
+        _identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        let mut server = self.server.lock().unwrap_or_else(PoisonError::into_inner);
+        if server.is_none() {
+            *server = Some(StandaloneServer::start(self.address)?);
+        }
+        if let Some(server) = server.as_ref() {
+            server.add_token(&challenge.token, key_authorization);
+        }
+
+        Ok(())
+    }
+
+    fn cleanup(
+        &self,
+        _identifier: &str,
+        challenge: &Challenge,
+        _key_authorization: &str,
+    ) -> Result<()> {
+        let server = self.server.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(server) = server.as_ref() {
+            server.remove_token(&challenge.token);
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes the http challenge by writing the token to the web root of an already running
+/// server, below `.well-known/acme-challenge`. The token file is removed again afterwards.
+#[derive(Debug, Default)]
+pub struct WebrootSolver {
+    webroots: HashMap<String, PathBuf>,
+    default_webroot: Option<PathBuf>,
+}
+
+impl WebrootSolver {
+    /// Creates a solver that writes the tokens of all domains to the same web root.
+    pub fn new<P: Into<PathBuf>>(webroot: P) -> Self {
+        WebrootSolver {
+            webroots: HashMap::new(),
+            default_webroot: Some(webroot.into()),
+        }
+    }
+
+    /// Creates a solver with a separate web root for every domain, e.g. for servers with
+    /// a document root per virtual host. Wildcard domains are given with or without the `*.`
+    /// prefix.
+    pub fn for_domains(webroots: HashMap<String, PathBuf>) -> Self {
+        WebrootSolver {
+            webroots: webroots
+                .into_iter()
+                .map(|(domain, webroot)| (identifier_of(&domain), webroot))
+                .collect(),
+            default_webroot: None,
+        }
+    }
+
+    /// Returns the path of the token file for the domain.
+    fn token_file(&self, identifier: &str, challenge: &Challenge) -> Result<PathBuf> {
+        let webroot = self
+            .webroots
+            .get(identifier)
+            .or(self.default_webroot.as_ref())
+            .ok_or(Error::NoWebServer)?;
+
+        Ok(webroot.join(CHALLENGE_PATH).join(&challenge.token))
+    }
+}
+
+impl ChallengeSolver for WebrootSolver {
+    fn challenge_types(&self, _identifier: &str) -> &[&str] {
+        &[HTTP_01]
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        let token_file = self.token_file(identifier, challenge)?;
+        if let Some(challenge_dir) = token_file.parent() {
+            fs::create_dir_all(challenge_dir)?;
+        }
+        fs::write(&token_file, key_authorization)?;
+
+        Ok(())
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        _key_authorization: &str,
+    ) -> Result<()> {
+        let token_file = self.token_file(identifier, challenge)?;
+
+        // a leftover token doesn't affect the certificate, so it doesn't fail the order
+        match fs::remove_file(&token_file) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => warn!(
+                "Unable to remove the challenge token {}: {}",
+                token_file.display(),
+                error
+            ),
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes the dns challenge by handing the `TXT` record to a `DnsPublisher`.
+pub struct DnsSolver<'a> {
+    publisher: &'a dyn DnsPublisher,
+}
+
+impl<'a> DnsSolver<'a> {
+    /// Creates a solver that places the records with the publisher.
+    pub fn new(publisher: &'a dyn DnsPublisher) -> Self {
+        DnsSolver { publisher }
+    }
+}
+
+impl ChallengeSolver for DnsSolver<'_> {
+    fn challenge_types(&self, _identifier: &str) -> &[&str] {
+        &[DNS_01]
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        _challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        let record_name = dns::record_name(identifier);
+        let record_value = dns::txt_record_value(key_authorization);
+
+        self.publisher.publish(&record_name, &record_value)?;
+        self.publisher
+            .wait_for_propagation(&record_name, &record_value)
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        _challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        self.publisher.remove(
+            &dns::record_name(identifier),
+            &dns::txt_record_value(key_authorization),
+        )
+    }
+}
+
+/// Completes the tls-alpn challenge by serving a self-signed validation certificate on port 443
+/// to clients that negotiate the `acme-tls/1` protocol.
+#[derive(Default)]
+pub struct TlsAlpnSolver {
+    responder: Mutex<Option<TlsAlpnResponder>>,
+}
+
+impl TlsAlpnSolver {
+    /// Creates a solver, the responder is only started while a challenge is presented.
+    pub fn new() -> Self {
+        TlsAlpnSolver::default()
+    }
+}
+
+impl ChallengeSolver for TlsAlpnSolver {
+    fn challenge_types(&self, _identifier: &str) -> &[&str] {
+        &[TLS_ALPN_01]
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        _challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        let mut responder = self
+            .responder
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // the validation certificate is issued for a single domain, so a previous responder
+        // needs to free the port first
+        if let Some(previous) = responder.take() {
+            previous.stop();
+        }
+        *responder = Some(TlsAlpnResponder::start(identifier, key_authorization)?);
+
+        Ok(())
+    }
+
+    fn cleanup(
+        &self,
+        _identifier: &str,
+        _challenge: &Challenge,
+        _key_authorization: &str,
+    ) -> Result<()> {
+        let responder = self
+            .responder
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(responder) = responder {
+            responder.stop();
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes the challenges by running external commands, e.g. scripts that place a record at
+/// a `DNS` provider or configure a load balancer. The commands are run by the shell and get the
+/// challenge in the environment variables:
+/// * `ACME_DOMAIN` -> the domain of the authorization,
+/// * `ACME_CHALLENGE_TYPE` -> the type of the challenge, e.g. `http-01`,
+/// * `ACME_TOKEN` -> the token of the challenge,
+/// * `ACME_KEY_AUTHORIZATION` -> the key authorization that's served for the http challenge,
+/// * `ACME_TXT_NAME` -> the name of the `TXT` record for the dns challenge,
+/// * `ACME_TXT_VALUE` -> the value of the `TXT` record for the dns challenge.
+///
+/// The auth hook needs to return once the challenge can be validated, e.g. after the record
+/// propagated. A failing hook (non-zero exit status) fails the authorization.
+#[derive(Debug, Clone)]
+pub struct HookSolver {
+    challenge_types: Vec<&'static str>,
+    auth_hook: String,
+    cleanup_hook: Option<String>,
+}
+
+impl HookSolver {
+    /// Creates a solver that completes the challenges of the type (e.g. `DNS_01`) with the
+    /// auth hook and optionally removes them again with the cleanup hook.
+    pub fn new(challenge_type: &'static str, auth_hook: &str, cleanup_hook: Option<&str>) -> Self {
+        HookSolver {
+            challenge_types: vec![challenge_type],
+            auth_hook: auth_hook.to_owned(),
+            cleanup_hook: cleanup_hook.map(str::to_owned),
+        }
+    }
+
+    /// Runs the hook with the challenge in the environment and waits for it to finish.
+    fn run(
+        hook: &str,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        #[cfg(unix)]
+        let mut command = Command::new("sh");
+        #[cfg(unix)]
+        command.arg("-c");
+        #[cfg(windows)]
+        let mut command = Command::new("cmd");
+        #[cfg(windows)]
+        command.arg("/C");
+
+        let status = command
+            .arg(hook)
+            .env("ACME_DOMAIN", identifier)
+            .env("ACME_CHALLENGE_TYPE", &challenge.challenge_type)
+            .env("ACME_TOKEN", &challenge.token)
+            .env("ACME_KEY_AUTHORIZATION", key_authorization)
+            .env("ACME_TXT_NAME", dns::record_name(identifier))
+            .env("ACME_TXT_VALUE", dns::txt_record_value(key_authorization))
+            .status()?;
+
+        if !status.success() {
+            return Err(Error::HookFailed(hook.to_owned(), status));
+        }
+
+        Ok(())
+    }
+}
+
+impl ChallengeSolver for HookSolver {
+    fn challenge_types(&self, _identifier: &str) -> &[&str] {
+        &self.challenge_types
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        HookSolver::run(&self.auth_hook, identifier, challenge, key_authorization)
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        match &self.cleanup_hook {
+            Some(hook) => HookSolver::run(hook, identifier, challenge, key_authorization),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Completes the challenges of some domains with their own solvers, e.g. the dns challenge for
+/// a wildcard domain while the other domains use the http challenge. All other domains are
+/// completed with the default solver.
+pub struct PerDomainSolver<'a> {
+    default: Box<dyn ChallengeSolver + 'a>,
+    solvers: HashMap<String, Box<dyn ChallengeSolver + 'a>>,
+}
+
+impl<'a> PerDomainSolver<'a> {
+    /// Creates a solver that completes the challenges of all domains with the default solver.
+    pub fn new<S: ChallengeSolver + 'a>(default: S) -> Self {
+        PerDomainSolver {
+            default: Box::new(default),
+            solvers: HashMap::new(),
+        }
+    }
+
+    /// Completes the challenges of the domain with the solver. Wildcard domains are given
+    /// with or without the `*.` prefix.
+    pub fn insert<S: ChallengeSolver + 'a>(&mut self, domain: &str, solver: S) {
+        self.solvers.insert(identifier_of(domain), Box::new(solver));
+    }
+
+    /// Returns the solver for the identifier.
+    fn solver(&self, identifier: &str) -> &(dyn ChallengeSolver + 'a) {
+        self.solvers
+            .get(identifier)
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
+
+impl ChallengeSolver for PerDomainSolver<'_> {
+    fn challenge_types(&self, identifier: &str) -> &[&str] {
+        self.solver(identifier).challenge_types(identifier)
+    }
+
+    fn present(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        self.solver(identifier)
+            .present(identifier, challenge, key_authorization)
+    }
+
+    fn cleanup(
+        &self,
+        identifier: &str,
+        challenge: &Challenge,
+        key_authorization: &str,
+    ) -> Result<()> {
+        self.solver(identifier)
+            .cleanup(identifier, challenge, key_authorization)
+    }
+}
+
+/// Returns the identifier the server uses for the authorization of a domain, which is in lower
+/// case and has no `*.` prefix for wildcard domains (RFC8555 section 7.1.4).
+fn identifier_of(domain: &str) -> String {
+    domain
+        .strip_prefix("*.")
+        .unwrap_or(domain)
+        .to_ascii_lowercase()
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{mpsc::Sender, Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use crate::error::Result;
+
+/// The path below which the tokens of the http-01 challenge are served (RFC8555 section 8.3).
+const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+/// A `HTTP` server that answers the http-01 challenge for every token it was handed, so all
+/// authorizations of an order are served by the same server. The server is stopped when it's
+/// dropped, which also frees the port if the order failed.
+pub(crate) struct StandaloneServer {
+    tokens: Arc<Mutex<HashMap<String, String>>>,
+    stop: Sender<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StandaloneServer {
+    /// Binds the server to the address and starts serving in a background thread. The address
+    /// may be an IPv4 or IPv6 address, e.g. `0.0.0.0:80` or `[::]:8402`.
+    pub(crate) fn start(address: SocketAddr) -> Result<Self> {
+        let tokens: Arc<Mutex<HashMap<String, String>>> = Arc::default();
+        let served_tokens = Arc::clone(&tokens);
+
+        let server = rouille::Server::new(address, move |request| {
+            let key_authorization =
+                request
+                    .raw_url()
+                    .strip_prefix(CHALLENGE_PATH)
+                    .and_then(|token| {
+                        served_tokens
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get(token)
+                            .cloned()
+                    });
+
+            match key_authorization {
+                Some(key_authorization) => rouille::Response::text(key_authorization),
+                None => rouille::Response::empty_404(),
+            }
+        })
+        .map_err(io::Error::other)?;
+        let (handle, stop) = server.stoppable();
+
+        Ok(StandaloneServer {
+            tokens,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Serves the key authorization under the path of the token.
+    pub(crate) fn add_token(&self, token: &str, key_authorization: &str) {
+        self.tokens
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(token.to_owned(), key_authorization.to_owned());
+    }
+
+    /// Stops serving the token, e.g. once its authorization is final.
+    pub(crate) fn remove_token(&self, token: &str) {
+        self.tokens
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(token);
+    }
+}
+
+/// Stops the server and waits for the server thread to finish, so the port is free again.
+impl Drop for StandaloneServer {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use openssl::{
+    asn1::{Asn1Integer, Asn1Object, Asn1OctetString, Asn1Time},
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    sha::sha256,
+    ssl::{select_next_proto, AlpnError, SslAcceptor, SslMethod},
+    x509::{extension::SubjectAlternativeName, X509Extension, X509NameBuilder, X509},
+};
+
+use crate::error::Result;
+
+/// The address the responder listens on, the challenge is always validated on port 443.
+const TLS_ALPN_ADDRESS: &str = "0.0.0.0:443";
+/// The `acme-tls/1` protocol id in the wire format used by ALPN (RFC8737 section 6.2).
+const ACME_TLS_ALPN_PROTOCOL: &[u8] = b"\x0aacme-tls/1";
+/// The object identifier of the `id-pe-acmeIdentifier` extension (RFC8737 section 6.1).
+const ACME_IDENTIFIER_OID: &str = "1.3.6.1.5.5.7.1.31";
+/// The number of days the self-signed validation certificate is valid.
+const VALIDATION_CERT_DAYS: u32 = 7;
+/// The time the responder sleeps between polling for new connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The time after which a stalled handshake is aborted.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A `TLS` server on port 443 that presents the validation certificate for
+/// the tls-alpn-01 challenge to every client negotiating `acme-tls/1`.
+pub(crate) struct TlsAlpnResponder {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl TlsAlpnResponder {
+    /// Generates the validation certificate for the domain and starts serving it.
+    pub(crate) fn start(domain: &str, key_authorization: &str) -> Result<Self> {
+        let (private_key, certificate) = validation_certificate(domain, key_authorization)?;
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server())?;
+        acceptor.set_private_key(&private_key)?;
+        acceptor.set_certificate(&certificate)?;
+        acceptor.set_alpn_select_callback(|_, client_protos| {
+            select_next_proto(ACME_TLS_ALPN_PROTOCOL, client_protos).ok_or(AlpnError::ALERT_FATAL)
+        });
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind(TLS_ALPN_ADDRESS)?;
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            while !stop_flag.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if stream.set_nonblocking(false).is_err()
+                            || stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err()
+                        {
+                            continue;
+                        }
+                        // the validation is done once the handshake completed, so
+                        // the connection can be closed right away
+                        if let Ok(mut tls_stream) = acceptor.accept(stream) {
+                            let _ = tls_stream.shutdown();
+                        }
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(TlsAlpnResponder { stop, handle })
+    }
+
+    /// Stops the responder and waits for the server thread to finish.
+    pub(crate) fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+/// Creates the self-signed certificate for the tls-alpn-01 challenge. The certificate holds the
+/// domain as its only subject alternative name and the `SHA-256` digest of the key authorization
+/// in the critical `id-pe-acmeIdentifier` extension.
+pub(crate) fn validation_certificate(
+    domain: &str,
+    key_authorization: &str,
+) -> Result<(PKey<Private>, X509)> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let private_key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, domain)?;
+    let name = name.build();
+
+    let mut serial = BigNum::new()?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(Asn1Integer::from_bn(&serial)?.as_ref())?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&private_key)?;
+    builder.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+    builder.set_not_after(Asn1Time::days_from_now(VALIDATION_CERT_DAYS)?.as_ref())?;
+
+    let subject_alt_name = SubjectAlternativeName::new()
+        .dns(domain)
+        .build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(subject_alt_name)?;
+
+    // the extension value is the DER encoded octet string that holds the digest
+    let mut authorization = vec![0x04, 0x20];
+    authorization.extend_from_slice(&sha256(key_authorization.as_bytes()));
+    let acme_identifier = X509Extension::new_from_der(
+        Asn1Object::from_str(ACME_IDENTIFIER_OID)?.as_ref(),
+        true,
+        Asn1OctetString::new_from_bytes(&authorization)?.as_ref(),
+    )?;
+    builder.append_extension(acme_identifier)?;
+
+    builder.sign(&private_key, MessageDigest::sha256())?;
+
+    Ok((private_key, builder.build()))
+}
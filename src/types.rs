@@ -1,35 +1,37 @@
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+use std::collections::HashMap;
+use std::time::Duration;
 
 use core::fmt::Debug;
 use openssl::{
     hash::MessageDigest,
     nid::Nid,
-    pkey::{Private, Public},
-    rsa::Rsa,
-    sha::Sha256,
-    x509::{X509NameBuilder, X509Req, X509ReqBuilder},
+    pkey::{PKey, Private},
+    stack::Stack,
+    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509Req, X509ReqBuilder, X509},
 };
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
-    error::{Error, Result},
+    account::ExternalAccountBinding,
+    certificate::Certificate,
+    error::{Error, Problem, Result},
+    nonce::NoncePool,
+    renewal::{RenewalInfo, RenewalInfoResponse},
+    solver::{ChallengeSolver, DNS_01, HTTP_01, TLS_ALPN_01},
     util::{
-        b64, check_for_existing_server, extract_payload_and_nonce,
-        extract_payload_location_and_nonce, jwk, jws,
+        b64, check_response, extract_links, extract_location, jwk, jws, key_authorization, poll,
+        post_as_get, retry_after,
     },
 };
 
 pub type Nonce = String;
-pub type Certificate = String;
 
 /// The current status of the request. The status gets send from
 /// the server in every response and shows the progress as well as
 /// possible errors.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StatusType {
     #[serde(rename = "valid")]
     Valid,
@@ -37,6 +39,16 @@ pub enum StatusType {
     Pending,
     #[serde(rename = "invalid")]
     Invalid,
+    #[serde(rename = "ready")]
+    Ready,
+    #[serde(rename = "processing")]
+    Processing,
+    #[serde(rename = "deactivated")]
+    Deactivated,
+    #[serde(rename = "expired")]
+    Expired,
+    #[serde(rename = "revoked")]
+    Revoked,
 }
 
 /// The directory information that get returned in the first request
@@ -50,66 +62,169 @@ pub struct Directory {
     pub new_order: String,
     pub revoke_cert: String,
     pub key_change: String,
-    #[serde(skip)]
-    nonce: Nonce,
+    #[serde(default)]
+    pub meta: DirectoryMeta,
+    pub renewal_info: Option<String>,
+}
+
+/// The optional meta information about the server that's part of the directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryMeta {
+    pub terms_of_service: Option<String>,
+    pub website: Option<String>,
+    #[serde(default)]
+    pub caa_identities: Vec<String>,
+    #[serde(default)]
+    pub external_account_required: bool,
+    /// The certificate profiles offered by the server, mapped to their description.
+    #[serde(default)]
+    pub profiles: HashMap<String, String>,
 }
 
 impl Directory {
     /// Fetches the directory information from a specific server. This is the first request
     /// that's send to the server as it's return value holds information about the endpoints.
     pub fn fetch_dir(client: &Client, server_url: &str) -> Result<Self> {
-        let mut dir_infos: Self = client.get(server_url).send()?.json()?;
-
-        // fetch the new nonce
-        let nonce = client
-            .head(&dir_infos.new_nonce)
-            .send()?
-            .headers()
-            .get("replay-nonce")
-            .ok_or(Error::BadNonce)?
-            .to_str()?
-            .to_owned();
-
-        dir_infos.nonce = nonce;
-
-        Ok(dir_infos)
+        Ok(check_response(client.get(server_url).send()?)?.json()?)
     }
 
-    /// Creates a new account.
+    /// Creates a new account. If the CA requires it, the account is bound to an existing account
+    /// at the CA via the external account binding. Fails before registering if the server has
+    /// terms of service that weren't agreed to or requires an external account binding that's missing.
     pub fn create_account(
         &self,
         client: &Client,
-        p_key: &Rsa<Private>,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
         email: &str,
+        eab: Option<&ExternalAccountBinding>,
+        terms_of_service_agreed: bool,
     ) -> Result<Account> {
-        let jwk = jwk(p_key)?;
-        let header = json!({
-            "alg": "RS256",
-            "url": self.new_account,
-            "jwk": jwk,
-            "nonce": self.nonce,
-        });
+        if let Some(terms_of_service) = &self.meta.terms_of_service {
+            if !terms_of_service_agreed {
+                return Err(Error::TermsOfServiceNotAgreed(terms_of_service.clone()));
+            }
+        }
+        if self.meta.external_account_required && eab.is_none() {
+            return Err(Error::MissingExternalAccountBinding);
+        }
 
-        let payload = json!({
-            "termsOfServiceAgreed": true,
+        let mut payload = json!({
+            "termsOfServiceAgreed": terms_of_service_agreed,
             "contact": [format!("mailto:{}", email)]
         });
+        if let Some(eab) = eab {
+            payload["externalAccountBinding"] = eab.sign(p_key, &self.new_account)?;
+        }
+
+        let response = self.post_new_account(client, nonces, p_key, payload)?;
 
-        let payload = jws(payload, header, p_key)?;
+        let location = extract_location(&response)?;
+        let mut account: Account = response.json()?;
+        account.account_location = location;
 
-        let response = client
-            .post(&self.new_account)
-            .header("Content-Type", "application/jose+json")
-            .body(serde_json::to_string_pretty(&payload)?)
-            .send()?;
+        Ok(account)
+    }
 
-        let (location, nonce, mut account): (String, Nonce, Account) =
-            extract_payload_location_and_nonce(response)?;
+    /// Looks up the account that belongs to the given key without creating a new one
+    /// (`onlyReturnExisting`). Returns `None` if the server doesn't know the key.
+    pub fn find_account(
+        &self,
+        client: &Client,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
+    ) -> Result<Option<Account>> {
+        let payload = json!({ "onlyReturnExisting": true });
+
+        let response = match self.post_new_account(client, nonces, p_key, payload) {
+            Ok(response) => response,
+            Err(Error::AccountDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
 
-        account.nonce = nonce;
+        let location = extract_location(&response)?;
+        let mut account: Account = response.json()?;
         account.account_location = location;
 
-        Ok(account)
+        Ok(Some(account))
+    }
+
+    /// Fetches the renewal information of a certificate by its `ARI` identifier (RFC9773 section
+    /// 4.2). Returns `None` if the server doesn't support renewal information.
+    pub fn fetch_renewal_info(
+        &self,
+        client: &Client,
+        certificate_id: &str,
+    ) -> Result<Option<RenewalInfo>> {
+        let renewal_info_url = match &self.renewal_info {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let response = check_response(
+            client
+                .get(format!(
+                    "{}/{}",
+                    renewal_info_url.trim_end_matches('/'),
+                    certificate_id
+                ))
+                .send()?,
+        )?;
+        let retry_after = retry_after(&response);
+
+        let renewal_info: RenewalInfoResponse = response.json()?;
+        Ok(Some(renewal_info.into_renewal_info(retry_after)?))
+    }
+
+    /// Revokes a certificate (RFC8555 section 7.6). The request is either signed by the account
+    /// that holds the certificate, in which case its url is passed in as `account_url`, or by
+    /// the private key of the certificate itself, which is then identified by its `JWK`.
+    pub fn revoke_certificate(
+        &self,
+        client: &Client,
+        nonces: &NoncePool,
+        certificate: &X509,
+        reason: u8,
+        p_key: &PKey<Private>,
+        account_url: Option<&str>,
+    ) -> Result<()> {
+        let header = match account_url {
+            Some(account_url) => json!({
+                "url": self.revoke_cert,
+                "kid": account_url,
+            }),
+            None => json!({
+                "url": self.revoke_cert,
+                "jwk": jwk(p_key)?,
+            }),
+        };
+
+        let payload = json!({
+            "certificate": b64(&certificate.to_der()?),
+            "reason": reason,
+        });
+
+        nonces.post(client, &self.revoke_cert, header, payload, p_key)?;
+
+        Ok(())
+    }
+
+    /// Sends a request to the `newAccount` endpoint, which is signed with the `JWK` of the account key.
+    fn post_new_account(
+        &self,
+        client: &Client,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
+        payload: serde_json::Value,
+    ) -> Result<Response> {
+        let jwk = jwk(p_key)?;
+        let header = json!({
+            "url": self.new_account,
+            "jwk": jwk,
+        });
+
+        nonces.post(client, &self.new_account, header, payload, p_key)
     }
 }
 
@@ -121,185 +236,200 @@ pub struct Account {
     terms_of_service_agreed: Option<bool>,
     pub orders: Option<Vec<String>>,
     #[serde(skip)]
-    pub nonce: Nonce,
-    #[serde(skip)]
     pub account_location: String,
 }
 
 impl Account {
-    /// Creates a new order for issuing a dns certificate for a certain domain.
+    /// Creates an account from its url without asking the server for its details, e.g. for an
+    /// account whose url was saved in an `AccountStore`.
+    pub fn from_location(account_location: &str) -> Self {
+        Account {
+            status: "valid".to_owned(),
+            contact: None,
+            terms_of_service_agreed: None,
+            orders: None,
+            account_location: account_location.to_owned(),
+        }
+    }
+
+    /// Creates a new order for issuing a dns certificate for a list of domains. The order
+    /// contains one `dns` identifier per domain. When renewing a certificate, the `ARI`
+    /// identifier of the old certificate is passed in as `replaces` (RFC9773 section 5).
     pub fn create_new_order(
         &self,
         client: &Client,
+        nonces: &NoncePool,
         new_order_url: &str,
-        p_key: &Rsa<Private>,
-        domain: &str,
-        optional_csr: Option<X509Req>,
+        p_key: &PKey<Private>,
+        domains: &[String],
+        replaces: Option<&str>,
     ) -> Result<Order> {
         let header = json!({
-            "alg": "RS256",
             "url": new_order_url,
             "kid": self.account_location,
-            "nonce": self.nonce,
         });
 
-        let payload = json!({
-            "identifiers": [
-                { "type": "dns", "value": domain }
-            ],
-        });
+        let identifiers = domains
+            .iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect::<Vec<_>>();
 
-        let payload = jws(payload, header, p_key)?;
+        let mut payload = json!({ "identifiers": identifiers });
+        if let Some(replaces) = replaces {
+            payload["replaces"] = json!(replaces);
+        }
 
-        let response = client
-            .post(new_order_url)
-            .header("Content-Type", "application/jose+json")
-            .body(serde_json::to_string_pretty(&payload)?)
-            .send()?;
+        let response = nonces.post(client, new_order_url, header, payload, p_key)?;
 
-        let (nonce, mut order): (Nonce, Order) = extract_payload_and_nonce(response)?;
-        order.nonce = nonce;
-        order.optional_csr = optional_csr;
+        let location = extract_location(&response)?;
+        let mut order: Order = response.json()?;
+        order.location = location;
 
         Ok(order)
     }
+
+    /// Rolls the account over to a new key (RFC8555 section 7.3.5). The request consists of an
+    /// inner `JWS` signed by the new key, which is wrapped in an outer `JWS` signed by the old key.
+    pub fn change_key(
+        &self,
+        client: &Client,
+        nonces: &NoncePool,
+        key_change_url: &str,
+        old_key: &PKey<Private>,
+        new_key: &PKey<Private>,
+    ) -> Result<()> {
+        let inner_header = json!({
+            "url": key_change_url,
+            "jwk": jwk(new_key)?,
+        });
+        let inner_payload = json!({
+            "account": self.account_location,
+            "oldKey": jwk(old_key)?,
+        });
+        let payload = jws(inner_payload, inner_header, new_key)?;
+
+        let header = json!({
+            "url": key_change_url,
+            "kid": self.account_location,
+        });
+
+        nonces.post(client, key_change_url, header, payload, old_key)?;
+
+        Ok(())
+    }
 }
 
 /// Holds information about an `Order` in the `ACME` context.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Order {
-    pub status: String,
+    pub status: StatusType,
     pub expires: String,
     pub identifiers: serde_json::Value,
     pub authorizations: Vec<String>,
     pub finalize: String,
+    pub error: Option<Problem>,
     #[serde(skip)]
-    pub nonce: Nonce,
-    #[serde(skip)]
-    optional_csr: Option<X509Req>,
+    pub location: String,
 }
 
 impl Order {
-    /// Fetches the available authorisation options from the server for a certain order.
+    /// Fetches the available authorisation options from the server for one of the
+    /// `authorizations` of this order. As an order holds one authorization per domain,
+    /// this needs to be called for each of them.
     pub fn fetch_auth_challenges(
         &self,
         client: &Client,
+        auth_url: &str,
         account_url: &str,
-        p_key: &Rsa<Private>,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
     ) -> Result<ChallengeAuthorisation> {
-        let auth_url = self
-            .authorizations
-            .first()
-            .ok_or(Error::NoHttpChallengePresent)?
-            .to_string();
-
-        let header = json!({
-            "alg": "RS256",
-            "url": auth_url,
-            "kid": account_url,
-            "nonce": self.nonce,
-        });
-
-        let payload = json!("");
-
-        let jws = jws(payload, header, p_key)?;
+        let response = post_as_get(client, auth_url, account_url, nonces, p_key)?;
 
-        let response = client
-            .post(&auth_url)
-            .header("Content-Type", "application/jose+json")
-            .body(serde_json::to_string_pretty(&jws)?)
-            .send()?;
+        let mut challenge: ChallengeAuthorisation = response.json()?;
+        challenge.url = auth_url.to_owned();
 
-        let (nonce, mut challenge): (Nonce, ChallengeAuthorisation) =
-            extract_payload_and_nonce(response)?;
+        Ok(challenge)
+    }
 
-        challenge.nonce = nonce;
+    /// Polls the order until all of its authorizations are processed and it's `ready` to be
+    /// finalized.
+    pub fn wait_until_ready(
+        &self,
+        client: &Client,
+        account_url: &str,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
+        timeout: Duration,
+    ) -> Result<()> {
+        let order = poll(
+            client,
+            &self.location,
+            account_url,
+            nonces,
+            p_key,
+            timeout,
+            |order: &Order| order.status != StatusType::Pending,
+        )?;
 
-        Ok(challenge)
+        match (order.status, order.error) {
+            (StatusType::Ready, _) | (StatusType::Valid, _) => Ok(()),
+            (_, Some(problem)) => Err(problem.into()),
+            (_, None) => Err(Error::InvalidOrder),
+        }
     }
 
-    /// Finalizes an order whose challenge was already done. This returns an `UpdatedOrder` object which
-    /// is able to download the issued certificate. This method `panics` if the challenge was not yet completed.
+    /// Finalizes an order whose challenge was already done with a certificate signing request.
+    /// This returns an `UpdatedOrder` object which is able to download the issued certificate once
+    /// it's `valid`. The order needs to be `ready`, see `Order::wait_until_ready`.
     pub fn finalize_order(
         self,
         client: &Client,
         account_url: &str,
-        new_nonce: Nonce,
-        p_key: &Rsa<Private>,
-        cert_keypair: &(Rsa<Private>, Rsa<Public>),
-        domain: &str,
+        nonces: &NoncePool,
+        p_key: &PKey<Private>,
+        csr: &X509Req,
     ) -> Result<UpdatedOrder> {
         let header = json!({
-        "alg": "RS256",
-        "url": self.finalize,
-        "kid": account_url,
-        "nonce": new_nonce,
+            "url": self.finalize,
+            "kid": account_url,
         });
 
-        let csr = if let Some(csr) = self.optional_csr {
-            csr
-        } else {
-            Order::request_csr(cert_keypair, domain.to_owned())?
-        };
-
         let csr_string = b64(&csr.to_der()?);
 
         let payload = json!({ "csr": csr_string });
 
-        let jws = jws(payload, header, p_key)?;
+        let response = nonces.post(client, &self.finalize, header, payload, p_key)?;
 
-        let response = client
-            .post(&self.finalize)
-            .header("Content-Type", "application/jose+json")
-            .header("Accept", "application/pem-certificate-chain")
-            .body(serde_json::to_string_pretty(&jws)?)
-            .send()?;
-
-        let (nonce, mut updated_order): (Nonce, UpdatedOrder) =
-            extract_payload_and_nonce(response)?;
-
-        updated_order.nonce = nonce;
+        let mut updated_order: UpdatedOrder = response.json()?;
+        updated_order.location = self.location;
 
         Ok(updated_order)
     }
 
-    /// Factors a csr request, which needs to be sent during finalization.
-    fn request_csr(keypair: &(Rsa<Private>, Rsa<Public>), common_name: String) -> Result<X509Req> {
+    /// Factors a csr request, which needs to be sent during finalization. The first domain
+    /// is used as the common name, while all domains are listed in the subject alternative names.
+    pub fn request_csr(key: &PKey<Private>, domains: &[String]) -> Result<X509Req> {
         let mut request = X509ReqBuilder::new()?;
         let mut c_name = X509NameBuilder::new()?;
 
-        let pri_key = &openssl::pkey::PKey::private_key_from_pem(&keypair.0.private_key_to_pem()?)?;
-        let public_key =
-            &openssl::pkey::PKey::public_key_from_pem(&keypair.1.public_key_to_pem()?)?;
-
-        c_name.append_entry_by_nid(Nid::COMMONNAME, &common_name)?;
+        let common_name = domains.first().ok_or(Error::NoDomains)?;
+        c_name.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
         let name = c_name.build();
-        request.set_pubkey(public_key)?;
+        request.set_pubkey(key)?;
         request.set_subject_name(name.as_ref())?;
-        request.sign(pri_key, MessageDigest::sha256())?;
 
-        Ok(request.build())
-    }
-}
+        let mut subject_alt_name = SubjectAlternativeName::new();
+        for domain in domains {
+            subject_alt_name.dns(domain);
+        }
+        let mut extensions = Stack::new()?;
+        extensions.push(subject_alt_name.build(&request.x509v3_context(None))?)?;
+        request.add_extensions(&extensions)?;
+
+        request.sign(key, MessageDigest::sha256())?;
 
-impl Debug for Order {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Order")
-            .field("status", &self.status)
-            .field("expires", &self.expires)
-            .field("identifiers", &self.identifiers)
-            .field("authorizations", &self.authorizations)
-            .field("finalize", &self.finalize)
-            .field("nonce", &self.nonce)
-            .field(
-                "optional_csr",
-                if self.optional_csr.is_some() {
-                    &"Some(Csr)"
-                } else {
-                    &"None"
-                },
-            )
-            .finish()
+        Ok(request.build())
     }
 }
 
@@ -311,6 +441,7 @@ pub struct Challenge {
     #[serde(rename = "type")]
     pub challenge_type: String,
     pub url: String,
+    pub error: Option<Problem>,
 }
 
 /// Holds information about the authentification options in the `ACME` context.
@@ -323,154 +454,227 @@ pub struct ChallengeAuthorisation {
     pub challenges: Vec<Challenge>,
     pub wildcard: Option<bool>,
     #[serde(skip)]
-    pub nonce: Nonce,
+    pub url: String,
 }
 
 impl ChallengeAuthorisation {
-    /// Completes the http challenge by opening an `http` server which returns the needed token
-    /// under the specified path.
-    pub fn complete_http_challenge(
+    /// Returns the domain this authorization was issued for. For wildcard domains the server
+    /// returns the identifier without the `*.` prefix, which is stripped here as well in case
+    /// a server doesn't follow this.
+    pub fn domain(&self) -> Result<&str> {
+        let domain = self.identifier["value"]
+            .as_str()
+            .ok_or(Error::UnexpectedResponse)?;
+
+        Ok(domain.strip_prefix("*.").unwrap_or(domain))
+    }
+
+    /// Returns whether the authorization belongs to a wildcard domain. These can only be
+    /// validated with the dns challenge.
+    pub fn is_wildcard(&self) -> bool {
+        self.wildcard.unwrap_or(false)
+    }
+
+    /// Completes one of the challenges the solver supports, the first supported type in the
+    /// order of preference of the solver is chosen. The key authorization is presented with the
+    /// solver and the authorization is polled until it's validated. Afterwards the solver
+    /// cleans up again, even if the validation failed.
+    pub fn complete_challenge(
         self,
         client: &Client,
+        nonces: &NoncePool,
         account_url: &str,
-        p_key: &Rsa<Private>,
-        standalone: bool,
-    ) -> Result<Nonce> {
-        let http_challenge = self
-            .challenges
-            .into_iter()
-            .find(|challenge| challenge.challenge_type == "http-01")
-            .ok_or(Error::NoHttpChallengePresent)?;
-
-        ChallengeAuthorisation::complete_challenge(
-            client,
-            http_challenge,
-            self.nonce,
-            account_url,
-            p_key,
-            standalone,
-        )
+        p_key: &PKey<Private>,
+        solver: &dyn ChallengeSolver,
+        timeout: Duration,
+    ) -> Result<()> {
+        let identifier = self.domain()?;
+        let challenge_types = solver.challenge_types(identifier);
+        let challenge = challenge_types
+            .iter()
+            .find_map(|challenge_type| {
+                self.challenges
+                    .iter()
+                    .find(|challenge| challenge.challenge_type == *challenge_type)
+            })
+            .ok_or_else(|| missing_challenge(challenge_types))?;
+
+        let key_authorization = key_authorization(&challenge.token, p_key)?;
+
+        // the key authorization needs to be presented before the server is asked to check it.
+        // A solver may have presented parts of it before failing, so it's always cleaned up
+        let result = solver
+            .present(identifier, challenge, &key_authorization)
+            .and_then(|_| self.validate(client, nonces, challenge, account_url, p_key, timeout));
+        let cleaned_up = solver.cleanup(identifier, challenge, &key_authorization);
+
+        result.and(cleaned_up)
     }
 
-    /// Actually opens the server and kicks of the challenge.
-    fn complete_challenge(
+    /// Asks the server to validate the challenge and polls the authorization until
+    /// it's either valid or failed.
+    fn validate(
+        &self,
         client: &Client,
-        challenge_infos: Challenge,
-        nonce: Nonce,
+        nonces: &NoncePool,
+        challenge: &Challenge,
         acc_url: &str,
-        private_key: &Rsa<Private>,
-        standalone: bool,
-    ) -> Result<Nonce> {
-        const CHALLENGE_PATH: &str = ".well-known/acme-challenge";
-
-        let thumbprint = jwk(private_key)?;
-        let mut hasher = Sha256::new();
-        hasher.update(&thumbprint.to_string().into_bytes());
-        let thumbprint = hasher.finish();
-
-        let challenge_content = format!("{}.{}", challenge_infos.token, b64(&thumbprint));
+        private_key: &PKey<Private>,
+        timeout: Duration,
+    ) -> Result<()> {
+        ChallengeAuthorisation::kick_off_challenge(
+            client,
+            nonces,
+            challenge,
+            acc_url,
+            private_key,
+        )?;
 
-        let result = ChallengeAuthorisation::kick_off_http_challenge(
+        let authorisation = poll(
             client,
-            challenge_infos.clone(),
-            nonce,
+            &self.url,
             acc_url,
+            nonces,
             private_key,
+            timeout,
+            |authorisation: &ChallengeAuthorisation| authorisation.status != StatusType::Pending,
         )?;
 
-        if standalone {
-            std::thread::spawn(|| {
-                rouille::start_server("0.0.0.0:80", move |request| {
-                    if request.raw_url() == format!("/{}/{}", CHALLENGE_PATH, challenge_infos.token)
-                    {
-                        rouille::Response::text(challenge_content.clone())
-                    } else {
-                        rouille::Response::empty_404()
-                    }
-                });
+        if authorisation.status != StatusType::Valid {
+            // the server attaches the reason of the failure to the challenge
+            let problem = authorisation
+                .challenges
+                .into_iter()
+                .find(|polled| polled.url == challenge.url)
+                .and_then(|polled| polled.error);
+
+            return Err(match problem {
+                Some(problem) => problem.into(),
+                None => Error::InvalidAuthorization(self.domain()?.to_owned()),
             });
-        } else if check_for_existing_server() {
-            const WEB_ROOT: &str = "/var/www/html";
-
-            let full_path = Path::new(WEB_ROOT).join(CHALLENGE_PATH);
-            fs::create_dir_all(full_path.clone())?;
-            let mut output = File::create(full_path.join(challenge_infos.token))?;
-            write!(output, "{}", challenge_content)?;
-        } else {
-            return Err(Error::NoWebServer);
         }
-        std::thread::sleep(std::time::Duration::from_secs(5));
-        Ok(result)
+
+        Ok(())
     }
 
     /// Requests the check of the server at the `ACME` server instance.
-    fn kick_off_http_challenge(
+    fn kick_off_challenge(
         client: &Client,
-        challenge_infos: Challenge,
-        nonce: Nonce,
+        nonces: &NoncePool,
+        challenge_infos: &Challenge,
         acc_url: &str,
-        private_key: &Rsa<Private>,
-    ) -> Result<Nonce> {
+        private_key: &PKey<Private>,
+    ) -> Result<()> {
         let header = json!({
-            "alg": "RS256",
             "kid": acc_url,
-            "nonce": nonce,
             "url": challenge_infos.url
         });
 
         let payload = json!({});
 
-        let jws = jws(payload, header, private_key)?;
-
-        Ok(client
-            .post(&challenge_infos.url)
-            .header("Content-Type", "application/jose+json")
-            .body(serde_json::to_string_pretty(&jws)?)
-            .send()?
-            .headers()
-            .get("replay-nonce")
-            .ok_or(Error::IncorrectResponse)?
-            .to_str()?
-            .to_owned())
+        nonces.post(client, &challenge_infos.url, header, payload, private_key)?;
+
+        Ok(())
+    }
+}
+
+/// Returns the error for an authorization that doesn't offer any of the challenge types.
+fn missing_challenge(challenge_types: &[&str]) -> Error {
+    match challenge_types {
+        [HTTP_01] => Error::NoHttpChallengePresent,
+        [DNS_01] => Error::NoDnsChallengePresent,
+        [TLS_ALPN_01] => Error::NoTlsAlpnChallengePresent,
+        _ => Error::NoSupportedChallengePresent(challenge_types.join(", ")),
     }
 }
 
 /// Holds information about a finalized order in the `ACME` context.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdatedOrder {
-    pub status: String,
+    pub status: StatusType,
     expires: String,
     identifiers: serde_json::Value,
     authorizations: serde_json::Value,
     finalize: String,
-    pub certificate: String,
+    pub certificate: Option<String>,
+    pub error: Option<Problem>,
     #[serde(skip)]
-    pub nonce: Nonce,
+    pub location: String,
 }
 
 impl UpdatedOrder {
-    /// Downloads an issued certificate.
+    /// Polls the order while the server is `processing` the finalization, until the
+    /// certificate is issued and the order is `valid`.
+    pub fn wait_until_valid(
+        self,
+        client: &Client,
+        nonces: &NoncePool,
+        account_url: &str,
+        p_key: &PKey<Private>,
+        timeout: Duration,
+    ) -> Result<UpdatedOrder> {
+        if self.status == StatusType::Valid {
+            return Ok(self);
+        }
+
+        let mut updated_order = poll(
+            client,
+            &self.location,
+            account_url,
+            nonces,
+            p_key,
+            timeout,
+            |order: &UpdatedOrder| {
+                order.status != StatusType::Processing && order.status != StatusType::Ready
+            },
+        )?;
+
+        if updated_order.status != StatusType::Valid {
+            return Err(match updated_order.error {
+                Some(problem) => problem.into(),
+                None => Error::InvalidOrder,
+            });
+        }
+
+        updated_order.location = self.location;
+
+        Ok(updated_order)
+    }
+
+    /// Downloads an issued certificate. Servers may offer alternate chains for the certificate
+    /// (RFC8555 section 7.4.2), which are leading to different roots. If a preferred chain is given,
+    /// the first chain whose topmost certificate is issued by this common name is returned, i.e. the
+    /// chain leading to this root. Otherwise or if no chain matches, the default chain is returned.
     pub fn download_certificate(
         &self,
         client: &Client,
+        nonces: &NoncePool,
         account_url: &str,
-        p_key: &Rsa<Private>,
+        p_key: &PKey<Private>,
+        preferred_chain: Option<&str>,
     ) -> Result<Certificate> {
-        let header = json!({
-            "alg": "RS256",
-            "url": self.certificate,
-            "kid": account_url,
-            "nonce": self.nonce,
-        });
-        let payload = json!("");
+        let certificate_url = self.certificate.as_ref().ok_or(Error::UnexpectedResponse)?;
+
+        let response = post_as_get(client, certificate_url, account_url, nonces, p_key)?;
+        let alternates = extract_links(&response, "alternate");
+        let default_chain = Certificate::from_pem(&response.bytes()?)?;
 
-        let jws = jws(payload, header, p_key)?;
+        let preferred_chain = match preferred_chain {
+            Some(preferred_chain) => preferred_chain,
+            None => return Ok(default_chain),
+        };
+        if default_chain.root_issuer().as_deref() == Some(preferred_chain) {
+            return Ok(default_chain);
+        }
+
+        for alternate_url in alternates {
+            let response = post_as_get(client, &alternate_url, account_url, nonces, p_key)?;
+            let chain = Certificate::from_pem(&response.bytes()?)?;
+            if chain.root_issuer().as_deref() == Some(preferred_chain) {
+                return Ok(chain);
+            }
+        }
 
-        Ok(client
-            .post(&self.certificate)
-            .header("Content-Type", "application/jose+json")
-            .body(serde_json::to_string_pretty(&jws)?)
-            .send()?
-            .text()?)
+        Ok(default_chain)
     }
 }
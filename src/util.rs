@@ -1,21 +1,35 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use base64::encode_config;
 use openssl::{
-    hash::MessageDigest,
-    pkey::{PKey, Private, Public},
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey, EcKeyRef},
+    ecdsa::EcdsaSig,
+    hash::{hash, MessageDigest},
+    nid::Nid,
+    pkey::{HasParams, HasPublic, Id, PKey, PKeyRef, Private},
     rsa::{Padding, Rsa},
+    sha::sha256,
     sign::Signer,
-    x509::X509Req,
+    x509::{X509Req, X509},
 };
-use reqwest::blocking::Response;
+use reqwest::blocking::{Client, Response};
 use serde::de::DeserializeOwned;
 use serde_json::json;
 
 use crate::{
-    error::{Error, Result},
-    types::{Certificate, Nonce},
-    KEY_WIDTH,
+    certificate::Certificate,
+    error::{Error, Problem, Result},
+    nonce::NoncePool,
+    types::Nonce,
+    KeyType,
 };
 
 /// Check if a process if using port 80. A return value of false means there wasn't a process
@@ -30,53 +44,182 @@ pub fn check_for_existing_server() -> bool {
     TcpStream::connect(&addrs[..]).is_ok()
 }
 
-/// Generates a `RSA` private key.
-pub(crate) fn generate_rsa_key() -> Result<Rsa<Private>> {
-    Ok(Rsa::generate(KEY_WIDTH)?)
+/// Generates an `ECDSA` private key on the given curve, which needs to be either
+/// `Nid::X9_62_PRIME256V1` (P-256) or `Nid::SECP384R1` (P-384) to be used as an account key.
+pub fn generate_ec_key(curve: Nid) -> Result<PKey<Private>> {
+    let group = EcGroup::from_curve_name(curve)?;
+
+    Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+}
+
+/// Generates a private key of the given type, which can be used both as an account key
+/// and as the key of a certificate.
+pub fn generate_key(key_type: KeyType) -> Result<PKey<Private>> {
+    match key_type {
+        KeyType::Rsa2048 => Ok(PKey::from_rsa(Rsa::generate(2048)?)?),
+        KeyType::Rsa3072 => Ok(PKey::from_rsa(Rsa::generate(3072)?)?),
+        KeyType::Rsa4096 => Ok(PKey::from_rsa(Rsa::generate(4096)?)?),
+        KeyType::EcP256 => generate_ec_key(Nid::X9_62_PRIME256V1),
+        KeyType::EcP384 => generate_ec_key(Nid::SECP384R1),
+    }
+}
+
+/// The parameters of the elliptic curves that can be used for signing a `JWS` (RFC7518 section 3.4).
+struct EcParams {
+    crv: &'static str,
+    alg: &'static str,
+    digest: MessageDigest,
+    size: i32,
+}
+
+impl EcParams {
+    /// Looks up the parameters for the curve of an `ECDSA` key.
+    fn of<T: HasParams>(key: &EcKeyRef<T>) -> Result<Self> {
+        match key.group().curve_name() {
+            Some(Nid::X9_62_PRIME256V1) => Ok(EcParams {
+                crv: "P-256",
+                alg: "ES256",
+                digest: MessageDigest::sha256(),
+                size: 32,
+            }),
+            Some(Nid::SECP384R1) => Ok(EcParams {
+                crv: "P-384",
+                alg: "ES384",
+                digest: MessageDigest::sha384(),
+                size: 48,
+            }),
+            _ => Err(Error::UnsupportedKeyType),
+        }
+    }
 }
 
-/// Generates a `RSA` keypair.
-pub fn generate_rsa_keypair() -> Result<(Rsa<Private>, Rsa<Public>)> {
-    let rsa_key = generate_rsa_key()?;
-    Ok((
-        Rsa::private_key_from_pem(&rsa_key.private_key_to_pem()?)?,
-        Rsa::public_key_from_pem(&rsa_key.public_key_to_pem()?)?,
-    ))
+/// Returns the required members of the json web key `JWK` (RFC7517) of a RSA or ECDSA key,
+/// sorted in lexicographic order as needed for the thumbprint (RFC7638 section 3.2).
+fn jwk_members<T: HasPublic>(key: &PKeyRef<T>) -> Result<Vec<(&'static str, String)>> {
+    match key.id() {
+        Id::RSA => {
+            let rsa = key.rsa()?;
+
+            Ok(vec![
+                ("e", b64(&rsa.e().to_vec())),
+                ("kty", "RSA".to_owned()),
+                ("n", b64(&rsa.n().to_vec())),
+            ])
+        }
+        Id::EC => {
+            let ec = key.ec_key()?;
+            let params = EcParams::of(&ec)?;
+
+            let mut x = BigNum::new()?;
+            let mut y = BigNum::new()?;
+            let mut ctx = BigNumContext::new()?;
+            ec.public_key()
+                .affine_coordinates(ec.group(), &mut x, &mut y, &mut ctx)?;
+
+            Ok(vec![
+                ("crv", params.crv.to_owned()),
+                ("kty", "EC".to_owned()),
+                ("x", b64(&x.to_vec_padded(params.size)?)),
+                ("y", b64(&y.to_vec_padded(params.size)?)),
+            ])
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
 }
 
-/// Builds a json web key `JWK` (RFC7517) for a RSA private key.
+/// Builds a json web key `JWK` (RFC7517) for a RSA or ECDSA key.
 /// # Example
 /// ```ignore,rust
 /// use acme_rs::util::jwk;
-/// use openssl::Rsa;
+/// use openssl::{pkey::PKey, rsa::Rsa};
 ///
-/// let priv_key = Rsa::generate(2048).expect("Error while receiving private key");
+/// let priv_key = PKey::from_rsa(Rsa::generate(2048).expect("Error while receiving private key"))
+///     .expect("Error while converting the key");
 ///
-/// let jwk = jwk(priv_key).expect("Error while creating jwk");
+/// let jwk = jwk(&priv_key).expect("Error while creating jwk");
 /// ```
-pub fn jwk(private_key: &Rsa<Private>) -> Result<serde_json::Value> {
-    let e = b64(&private_key.e().to_vec());
-    let n = b64(&private_key.n().to_vec());
+pub fn jwk<T: HasPublic>(key: &PKeyRef<T>) -> Result<serde_json::Value> {
+    Ok(jwk_members(key)?
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), json!(value)))
+        .collect::<serde_json::Map<_, _>>()
+        .into())
+}
 
-    Ok(json!({
-        "e": e,
-        "n": n,
-        "kty": "RSA",
-    }))
+/// Computes the `base64url` encoded `SHA-256` thumbprint of the json web key of a RSA or ECDSA
+/// key (RFC7638). The thumbprint is part of the key authorization of every challenge.
+/// # Example
+/// ```
+/// use acme_rs::util::jwk_thumbprint;
+/// use openssl::{bn::BigNum, ec::{EcGroup, EcKey}, nid::Nid, pkey::PKey, rsa::Rsa};
+///
+/// let decode = |value: &str| base64::decode_config(value, base64::URL_SAFE_NO_PAD).unwrap();
+///
+/// // the RSA key from RFC7638 section 3.1
+/// let n = BigNum::from_slice(&decode(
+///     "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECP\
+///      ebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY\
+///      368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0f\
+///      M4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+/// ))
+/// .unwrap();
+/// let e = BigNum::from_slice(&decode("AQAB")).unwrap();
+/// let rsa_key = PKey::from_rsa(Rsa::from_public_components(n, e).unwrap()).unwrap();
+///
+/// assert_eq!(
+///     jwk_thumbprint(&rsa_key).unwrap(),
+///     "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+/// );
+///
+/// // the P-256 key from RFC7515 appendix A.3
+/// let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+/// let x = BigNum::from_slice(&decode("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU")).unwrap();
+/// let y = BigNum::from_slice(&decode("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0")).unwrap();
+/// let ec_key = PKey::from_ec_key(
+///     EcKey::from_public_key_affine_coordinates(&group, &x, &y).unwrap(),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(
+///     jwk_thumbprint(&ec_key).unwrap(),
+///     "oKIywvGUpTVTyxMQ3bwIIeQUudfr_CkLMjCE19ECD-U"
+/// );
+/// ```
+pub fn jwk_thumbprint<T: HasPublic>(key: &PKeyRef<T>) -> Result<String> {
+    let members = jwk_members(key)?
+        .into_iter()
+        .map(|(name, value)| format!("\"{}\":\"{}\"", name, value))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Ok(b64(&sha256(format!("{{{}}}", members).as_bytes())))
+}
+
+/// Returns the `JWS` algorithm that's used for signing with the key: RS256 for RSA keys,
+/// ES256 and ES384 for ECDSA keys on the P-256 and P-384 curve.
+pub fn jws_algorithm(private_key: &PKey<Private>) -> Result<&'static str> {
+    match private_key.id() {
+        Id::RSA => Ok("RS256"),
+        Id::EC => {
+            let ec = private_key.ec_key()?;
+            Ok(EcParams::of(&ec)?.alg)
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
 }
 
 /// Constructs a json web signature `JWS` (RFC7515) in the flattened `JSON` form for a specified
-/// payload. This involves signing the JWS with the RS256 algorithm.
+/// payload. The `alg` header parameter is set according to the type of the key, RSA keys sign
+/// with RS256 and ECDSA keys with ES256 or ES384.
 /// # Example
 /// ```ignore,rust
-/// use acme_rs::util::jws;
+/// use acme_rs::util::{generate_ec_key, jws};
+/// use openssl::nid::Nid;
 /// use serde_json::json;
-/// use openssl::Rsa;
 ///
 /// // get the private key, the jws header and the payload
-/// let priv_key = Rsa::generate(2048).expect("Error while receiving private key");
+/// let priv_key = generate_ec_key(Nid::X9_62_PRIME256V1).expect("Error while receiving private key");
 /// let header = json!({
-///    "alg": "RS256",
 ///    "nonce": "superRandom",
 /// });
 ///
@@ -91,9 +234,11 @@ pub fn jwk(private_key: &Rsa<Private>) -> Result<serde_json::Value> {
 /// ```
 pub fn jws(
     payload: serde_json::Value,
-    header: serde_json::Value,
-    private_key: &Rsa<Private>,
+    mut header: serde_json::Value,
+    private_key: &PKey<Private>,
 ) -> Result<serde_json::Value> {
+    header["alg"] = json!(jws_algorithm(private_key)?);
+
     // edge case when the payload needs to be empty, e.g. for
     // fetching the challenges or downloading the certificate
     let empty_payload = payload == json!("");
@@ -101,17 +246,13 @@ pub fn jws(
     let payload64 = b64(serde_json::to_string_pretty(&payload)?.as_bytes());
     let header64 = b64(serde_json::to_string_pretty(&header)?.as_bytes());
 
-    let p_key = PKey::private_key_from_pem(&private_key.private_key_to_pem()?)?;
-    let mut signer = Signer::new(MessageDigest::sha256(), &p_key)?;
-
-    signer.set_rsa_padding(Padding::PKCS1)?;
-    if empty_payload {
-        signer.update(format!("{}.", header64).as_bytes())?;
+    let signing_input = if empty_payload {
+        format!("{}.", header64)
     } else {
-        signer.update(format!("{}.{}", header64, payload64).as_bytes())?;
-    }
+        format!("{}.{}", header64, payload64)
+    };
 
-    let signature = b64(&signer.sign_to_vec()?);
+    let signature = b64(&sign(private_key, signing_input.as_bytes())?);
 
     Ok(json!({
         "protected": header64,
@@ -120,51 +261,173 @@ pub fn jws(
     }))
 }
 
+/// Signs the data with the key. ECDSA signatures are encoded as the concatenation of the
+/// fixed-length `R` and `S` values instead of DER, as required by RFC7518 section 3.4.
+fn sign(private_key: &PKey<Private>, data: &[u8]) -> Result<Vec<u8>> {
+    match private_key.id() {
+        Id::RSA => {
+            let mut signer = Signer::new(MessageDigest::sha256(), private_key)?;
+            signer.set_rsa_padding(Padding::PKCS1)?;
+            signer.update(data)?;
+
+            Ok(signer.sign_to_vec()?)
+        }
+        Id::EC => {
+            let ec = private_key.ec_key()?;
+            let params = EcParams::of(&ec)?;
+
+            let digest = hash(params.digest, data)?;
+            let signature = EcdsaSig::sign(&digest, &ec)?;
+
+            let mut raw = signature.r().to_vec_padded(params.size)?;
+            raw.extend(signature.s().to_vec_padded(params.size)?);
+
+            Ok(raw)
+        }
+        _ => Err(Error::UnsupportedKeyType),
+    }
+}
+
+/// Computes the key authorization for a challenge token, which is the token concatenated
+/// with the thumbprint of the account key (RFC8555 section 8.1).
+pub(crate) fn key_authorization(token: &str, private_key: &PKey<Private>) -> Result<String> {
+    Ok(format!("{}.{}", token, jwk_thumbprint(private_key)?))
+}
+
 /// Returns the `base64url` encoding of the input.
 pub(crate) fn b64(to_encode: &[u8]) -> String {
     encode_config(to_encode, base64::URL_SAFE_NO_PAD)
 }
 
-/// Extracts the payload and `replay-nonce` header field from a given http `Response`.
-#[inline]
-pub(crate) fn extract_payload_and_nonce<T>(response: Response) -> Result<(Nonce, T)>
-where
-    T: DeserializeOwned,
-{
-    let replay_nonce = response
+/// Checks whether the server answered with an error. Problem documents (RFC7807) are
+/// mapped to the matching variant of the `Error` enumeration.
+pub(crate) fn check_response(response: Response) -> Result<Response> {
+    let is_problem = response
         .headers()
-        .get("replay-nonce")
-        .ok_or(Error::IncorrectResponse)?
-        .to_str()?
-        .to_owned();
+        .get("content-type")
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/problem+json"));
+
+    if is_problem {
+        let problem: Problem = response.json()?;
+        return Err(problem.into());
+    }
 
-    Ok((replay_nonce, response.json()?))
+    if !response.status().is_success() {
+        return Err(Error::UnexpectedStatus(response.status()));
+    }
+
+    Ok(response)
 }
 
-/// Extracts the `location` and `replay-nonce` header field as well as
-/// the payload from a given http `Response`.
+/// Extracts the `replay-nonce` header field from a given http `Response`.
 #[inline]
-pub(crate) fn extract_payload_location_and_nonce<T>(
-    response: Response,
-) -> Result<(String, Nonce, T)>
-where
-    T: DeserializeOwned,
-{
-    let replay_nonce = response
+pub(crate) fn extract_nonce(response: &Response) -> Result<Nonce> {
+    Ok(response
         .headers()
         .get("replay-nonce")
-        .ok_or(Error::IncorrectResponse)?
+        .ok_or(Error::MissingHeader("replay-nonce"))?
         .to_str()?
-        .to_owned();
+        .to_owned())
+}
 
-    let location = response
+/// Extracts the `location` header field from a given http `Response`.
+#[inline]
+pub(crate) fn extract_location(response: &Response) -> Result<String> {
+    Ok(response
         .headers()
         .get("location")
-        .ok_or(Error::IncorrectResponse)?
+        .ok_or(Error::MissingHeader("location"))?
         .to_str()?
-        .to_owned();
+        .to_owned())
+}
+
+/// Extracts the urls of all `link` header fields with the given relation from a given http
+/// `Response`, e.g. `<https://example.org/cert/1>;rel="alternate"`.
+pub(crate) fn extract_links(response: &Response, relation: &str) -> Vec<String> {
+    response
+        .headers()
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|link| {
+            let mut parts = link.split(';').map(str::trim);
+            let url = parts.next()?.strip_prefix('<')?.strip_suffix('>')?;
+
+            parts
+                .filter_map(|parameter| parameter.strip_prefix("rel="))
+                .any(|rel| rel.trim_matches('"') == relation)
+                .then(|| url.to_owned())
+        })
+        .collect()
+}
 
-    Ok((location, replay_nonce, response.json()?))
+/// Sends a `POST-as-GET` request (RFC8555 section 6.3), which is used to fetch
+/// resources like authorizations, orders and certificates.
+pub(crate) fn post_as_get(
+    client: &Client,
+    url: &str,
+    acc_url: &str,
+    nonces: &NoncePool,
+    private_key: &PKey<Private>,
+) -> Result<Response> {
+    let header = json!({
+        "url": url,
+        "kid": acc_url,
+    });
+
+    nonces.post(client, url, header, json!(""), private_key)
+}
+
+/// Extracts the `retry-after` header field from a given http `Response`. The header holds
+/// either the number of seconds to wait or a http date.
+pub(crate) fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get("retry-after")?.to_str().ok()?;
+
+    match value.trim().parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()
+            .map(|date| date.duration_since(SystemTime::now()).unwrap_or_default()),
+    }
+}
+
+/// Polls a resource with `POST-as-GET` requests until `is_final` holds for it or the timeout
+/// is reached. Between two requests the time in the `retry-after` header is waited, or
+/// `POLL_INTERVAL` if the server didn't send one.
+pub(crate) fn poll<T, F>(
+    client: &Client,
+    url: &str,
+    acc_url: &str,
+    nonces: &NoncePool,
+    private_key: &PKey<Private>,
+    timeout: Duration,
+    is_final: F,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    F: Fn(&T) -> bool,
+{
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let response = post_as_get(client, url, acc_url, nonces, private_key)?;
+        let wait = retry_after(&response).unwrap_or(POLL_INTERVAL);
+
+        let resource: T = response.json()?;
+        if is_final(&resource) {
+            return Ok(resource);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::Timeout);
+        }
+
+        thread::sleep(wait.min(remaining));
+    }
 }
 
 /// Loads a PEM formatted certificate signing request (CSR) from
@@ -175,50 +438,145 @@ pub fn load_csr_from_file(path: &str) -> Result<X509Req> {
     Ok(X509Req::from_pem(&bytes)?)
 }
 
-/// Parses the certificate and writes them into to files:
+/// Loads a PEM formatted certificate from a file. If the file holds a whole certificate
+/// chain, the first certificate is returned.
+pub fn load_certificate_from_file(path: &str) -> Result<X509> {
+    let bytes = std::fs::read(path)?;
+
+    Ok(X509::from_pem(&bytes)?)
+}
+
+/// Writes the certificate into two files:
 /// * my_cert.crt -> the certificate issued for the request,
 /// * cert_chain.crt -> the certificate chain issued for the request.
-pub fn save_certificates(certificate_chain: Certificate) -> Result<()> {
-    // extract the first certificat (certificate for the specified domain)
-    let cert_me = certificate_chain
-        .lines()
-        .take_while(|line| !line.is_empty())
-        .map(|line| {
-            let mut line_with_end = line.to_owned();
-            line_with_end.push_str("\r\n");
-            line_with_end
-        })
-        .collect::<String>();
+pub fn save_certificates(certificate: &Certificate) -> Result<()> {
+    write_atomic(Path::new("my_cert.crt"), &certificate.leaf_to_pem()?, false)?;
+    write_atomic(Path::new("cert_chain.crt"), &certificate.to_pem()?, false)?;
 
-    // save the certs to files
-    std::fs::write("my_cert.crt", cert_me.into_bytes())?;
-    std::fs::write("cert_chain.crt", certificate_chain.into_bytes())?;
+    Ok(())
+}
+
+/// Saves the private key and its public key into two files `priv.pem` and `pub.pem`.
+pub fn save_keypair(key: &PKey<Private>) -> Result<()> {
+    write_atomic(
+        Path::new("priv.pem"),
+        &key.private_key_to_pem_pkcs8()?,
+        true,
+    )?;
+    write_atomic(Path::new("pub.pem"), &key.public_key_to_pem()?, false)?;
 
     Ok(())
 }
 
-/// Saves an rsa keypair into two files `priv.pem` and `pub.pem`.
-pub fn save_keypair(keypair: &(Rsa<Private>, Rsa<Public>)) -> Result<()> {
-    let private_key = keypair.0.private_key_to_pem()?;
-    let public_key = keypair.1.public_key_to_pem()?;
+/// Saves the certificate and its key into a sub directory of `output_dir` that's named after the
+/// domain, e.g. `certs/example.org`. Wildcard domains are stored as `_wildcard.example.org`.
+/// The directory contains the files:
+/// * cert.pem -> the certificate issued for the request,
+/// * chain.pem -> the intermediate certificates,
+/// * fullchain.pem -> the certificate followed by the intermediate certificates,
+/// * privkey.pem -> the private key of the certificate, which is only readable by the current user.
+///
+/// Returns the directory the files were written to.
+pub fn save_certificate_files(
+    output_dir: &Path,
+    domain: &str,
+    certificate: &Certificate,
+    key: &PKey<Private>,
+) -> Result<PathBuf> {
+    let directory = match domain.strip_prefix("*.") {
+        Some(domain) => output_dir.join(format!("_wildcard.{}", domain)),
+        None => output_dir.join(domain),
+    };
+    fs::create_dir_all(&directory)?;
 
-    std::fs::write("priv.pem", &private_key)?;
-    std::fs::write("pub.pem", &public_key)?;
+    // all files are written before any of them is replaced, so a failed write keeps the old
+    // files. The key is replaced last, as servers refuse to load a new key with an old certificate
+    let files = [
+        ("cert.pem", certificate.leaf_to_pem()?, false),
+        ("chain.pem", certificate.intermediates_to_pem()?, false),
+        ("fullchain.pem", certificate.to_pem()?, false),
+        ("privkey.pem", key.private_key_to_pem_pkcs8()?, true),
+    ];
+    let mut temp_paths = Vec::new();
+    for (name, contents, private) in &files {
+        match write_temp(&directory.join(name), contents, *private) {
+            Ok(temp_path) => temp_paths.push(temp_path),
+            Err(error) => {
+                for temp_path in temp_paths {
+                    let _ = fs::remove_file(temp_path);
+                }
+                return Err(error);
+            }
+        }
+    }
+    for ((name, _, _), temp_path) in files.iter().zip(temp_paths) {
+        fs::rename(temp_path, directory.join(name))?;
+    }
 
-    Ok(())
+    Ok(directory)
+}
+
+/// Writes a file atomically by writing a temporary file next to it and renaming it afterwards,
+/// so readers never see a partially written file. Private files are only readable by the
+/// current user.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    let temp_path = write_temp(path, contents, private)?;
+
+    fs::rename(&temp_path, path).map_err(|error| {
+        let _ = fs::remove_file(&temp_path);
+        error.into()
+    })
+}
+
+/// Writes the contents to a new temporary file next to the path and returns the path of the
+/// temporary file. The name is unique to the process and the call, so concurrent writers of the
+/// same file don't write to the same temporary file.
+fn write_temp(path: &Path, contents: &[u8], private: bool) -> Result<PathBuf> {
+    static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing file name"))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(if private { 0o600 } else { 0o644 });
+    }
+
+    let mut file = options.open(&temp_path)?;
+    if let Err(error) = file.write_all(contents).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error.into());
+    }
+
+    Ok(temp_path)
 }
 
-/// Loads a private key and a public key from the given files.
+/// Loads a PEM formatted RSA or ECDSA private key from a file, e.g. an existing account key.
+pub fn load_private_key_from_file(path: &str) -> Result<PKey<Private>> {
+    let bytes = std::fs::read(path)?;
+
+    Ok(PKey::private_key_from_pem(&bytes)?)
+}
+
+/// Loads a private key and a public key from the given files and checks that they belong together.
 /// The keys need to be safed in the `pem` format.
-pub fn load_keys_from_file(
-    path_to_private: &str,
-    path_to_public: &str,
-) -> Result<(Rsa<Private>, Rsa<Public>)> {
-    let priv_key = std::fs::read(path_to_private)?;
-    let pub_key = std::fs::read(path_to_public)?;
-
-    Ok((
-        Rsa::private_key_from_pem(&priv_key)?,
-        Rsa::public_key_from_pem(&pub_key)?,
-    ))
+pub fn load_keys_from_file(path_to_private: &str, path_to_public: &str) -> Result<PKey<Private>> {
+    let priv_key = PKey::private_key_from_pem(&std::fs::read(path_to_private)?)?;
+    let pub_key = PKey::public_key_from_pem(&std::fs::read(path_to_public)?)?;
+
+    if !priv_key.public_eq(&pub_key) {
+        return Err(Error::KeyMismatch);
+    }
+
+    Ok(priv_key)
 }